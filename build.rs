@@ -0,0 +1,286 @@
+//! Generates `endpoints.rs` in `OUT_DIR` from `spec/openapi.json`: an `Operation` enum and one
+//! parameter struct with a typed path builder per operation. See `src/endpoints.rs`.
+
+use serde_json::Value;
+use std::{env, error::Error, fmt::Write, fs, path::Path};
+
+const SPEC: &str = "spec/openapi.json";
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+struct Parameter {
+    /// Name in the `OpenAPI` document, e.g. `_fields`.
+    name: String,
+    /// Rust field name, e.g. `fields`.
+    field: String,
+    in_path: bool,
+    required: bool,
+}
+
+impl Parameter {
+    fn field_type(&self) -> &'static str {
+        match (self.in_path, self.field.as_str()) {
+            (true, "item_id" | "property_id") => "&'a EntityId",
+            _ if self.required => "&'a str",
+            _ => "Option<&'a str>",
+        }
+    }
+
+    /// The expression that renders the field into the path, with the same validation the
+    /// hand-written code applies.
+    fn path_value(&self) -> String {
+        let field = &self.field;
+        match field.as_str() {
+            "item_id" => format!("entity_id(self.{field}, \"items\", \"{field}\")?"),
+            "property_id" => format!("entity_id(self.{field}, \"properties\", \"{field}\")?"),
+            "language_code" => format!("Language::validated(self.{field})?"),
+            "site_id" => format!("SiteId::validated(self.{field})?"),
+            _ => format!("self.{field}"),
+        }
+    }
+}
+
+struct Operation {
+    id: String,
+    name: String,
+    method: String,
+    template: String,
+    parameters: Vec<Parameter>,
+}
+
+impl Operation {
+    fn parse(template: &str, method: &str, j: &Value) -> Result<Self, Box<dyn Error>> {
+        let id = j["operationId"]
+            .as_str()
+            .ok_or_else(|| format!("{method} {template} has no operationId"))?
+            .to_string();
+        let mut name = id.clone();
+        if let Some(first) = name.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
+        let parameters = j["parameters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|p| p["in"] == "path" || p["in"] == "query")
+            .filter_map(|p| {
+                let name = p["name"].as_str()?;
+                Some(Parameter {
+                    name: name.to_string(),
+                    field: name.trim_start_matches('_').replace('-', "_"),
+                    in_path: p["in"] == "path",
+                    required: p["required"] == true,
+                })
+            })
+            .collect();
+        Ok(Self {
+            id,
+            name,
+            method: method.to_ascii_uppercase(),
+            template: template.to_string(),
+            parameters,
+        })
+    }
+
+    /// The path relative to the versioned API root, e.g. `/entities/items/{item_id}`.
+    fn relative_path(&self) -> &str {
+        match self
+            .template
+            .strip_prefix("/v")
+            .and_then(|rest| rest.split_once('/'))
+        {
+            Some((version, _)) if version.bytes().all(|b| b.is_ascii_digit()) => {
+                &self.template[version.len() + 2..]
+            }
+            _ => &self.template,
+        }
+    }
+
+    fn lifetime(&self) -> &'static str {
+        if self.parameters.is_empty() {
+            ""
+        } else {
+            "<'a>"
+        }
+    }
+
+    fn write_struct(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(
+            out,
+            "/// Parameters of `{}`: `{} {}`.",
+            self.id, self.method, self.template
+        )?;
+        writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq)]")?;
+        if self.parameters.is_empty() {
+            writeln!(out, "pub struct {};", self.name)?;
+        } else {
+            writeln!(out, "pub struct {}<'a> {{", self.name)?;
+            for parameter in &self.parameters {
+                let kind = if parameter.in_path { "path" } else { "query" };
+                writeln!(
+                    out,
+                    "    /// The `{}` {kind} parameter.\n    pub {}: {},",
+                    parameter.name,
+                    parameter.field,
+                    parameter.field_type()
+                )?;
+            }
+            writeln!(out, "}}")?;
+        }
+        writeln!(out)?;
+        let lifetime = self.lifetime();
+        writeln!(
+            out,
+            "impl{lifetime} Endpoint for {}{lifetime} {{",
+            self.name
+        )?;
+        writeln!(
+            out,
+            "    const OPERATION: Operation = Operation::{};",
+            self.name
+        )?;
+        writeln!(out)?;
+        writeln!(out, "    fn path(&self) -> Result<String, RestApiError> {{")?;
+        let path_parameters: Vec<_> = self.parameters.iter().filter(|p| p.in_path).collect();
+        if path_parameters.is_empty() {
+            writeln!(out, "        Ok(\"{}\".to_string())", self.relative_path())?;
+        } else {
+            writeln!(out, "        Ok(format!(")?;
+            writeln!(out, "            \"{}\",", self.relative_path())?;
+            for parameter in path_parameters {
+                writeln!(
+                    out,
+                    "            {} = {},",
+                    parameter.field,
+                    parameter.path_value()
+                )?;
+            }
+            writeln!(out, "        ))")?;
+        }
+        writeln!(out, "    }}")?;
+        let query_parameters: Vec<_> = self.parameters.iter().filter(|p| !p.in_path).collect();
+        if !query_parameters.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "    fn query(&self) -> HashMap<String, String> {{")?;
+            writeln!(out, "        let mut query = HashMap::new();")?;
+            for parameter in query_parameters {
+                let (name, field) = (&parameter.name, &parameter.field);
+                if parameter.required {
+                    writeln!(
+                        out,
+                        "        query.insert(\"{name}\".to_string(), self.{field}.to_string());"
+                    )?;
+                } else {
+                    writeln!(out, "        if let Some(value) = self.{field} {{")?;
+                    writeln!(
+                        out,
+                        "            query.insert(\"{name}\".to_string(), value.to_string());"
+                    )?;
+                    writeln!(out, "        }}")?;
+                }
+            }
+            writeln!(out, "        query")?;
+            writeln!(out, "    }}")?;
+        }
+        writeln!(out, "}}")?;
+        writeln!(out)
+    }
+}
+
+/// Doc comment, name, return type and value of a generated `Operation` accessor.
+type Accessor = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&Operation) -> String,
+);
+
+fn write_enum(operations: &[Operation], out: &mut String) -> std::fmt::Result {
+    writeln!(
+        out,
+        "/// Every operation described by the bundled `OpenAPI` document."
+    )?;
+    writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]")?;
+    writeln!(out, "pub enum Operation {{")?;
+    for operation in operations {
+        writeln!(
+            out,
+            "    /// `{} {}`\n    {},",
+            operation.method, operation.template, operation.name
+        )?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "impl Operation {{")?;
+    writeln!(out, "    /// All operations, sorted by path template.")?;
+    writeln!(
+        out,
+        "    pub const ALL: [Operation; {}] = [",
+        operations.len()
+    )?;
+    for operation in operations {
+        writeln!(out, "        Operation::{},", operation.name)?;
+    }
+    writeln!(out, "    ];")?;
+    let accessors: [Accessor; 3] = [
+        (
+            "Returns the `operationId`, e.g. `getItem`.",
+            "operation_id",
+            "&'static str",
+            |o| format!("{:?}", o.id),
+        ),
+        (
+            "Returns the HTTP method.",
+            "method",
+            "reqwest::Method",
+            |o| format!("reqwest::Method::{}", o.method),
+        ),
+        (
+            "Returns the path template, e.g. `/v1/entities/items/{item_id}`.",
+            "path_template",
+            "&'static str",
+            |o| format!("{:?}", o.template),
+        ),
+    ];
+    for (doc, name, return_type, value) in accessors {
+        writeln!(out)?;
+        writeln!(out, "    /// {doc}")?;
+        writeln!(out, "    pub const fn {name}(self) -> {return_type} {{")?;
+        writeln!(out, "        match self {{")?;
+        for operation in operations {
+            writeln!(
+                out,
+                "            Operation::{} => {},",
+                operation.name,
+                value(operation)
+            )?;
+        }
+        writeln!(out, "        }}")?;
+        writeln!(out, "    }}")?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed={SPEC}");
+    let spec: Value = serde_json::from_str(&fs::read_to_string(SPEC)?)?;
+    let paths = spec["paths"]
+        .as_object()
+        .ok_or_else(|| format!("{SPEC} has no paths"))?;
+    let mut operations = vec![];
+    for (template, item) in paths {
+        for method in METHODS {
+            if let Some(j) = item.get(method) {
+                operations.push(Operation::parse(template, method, j)?);
+            }
+        }
+    }
+    let mut out = String::from("// Generated by build.rs from spec/openapi.json. Do not edit.\n\n");
+    write_enum(&operations, &mut out)?;
+    for operation in &operations {
+        operation.write_struct(&mut out)?;
+    }
+    let out_dir = env::var("OUT_DIR")?;
+    fs::write(Path::new(&out_dir).join("endpoints.rs"), out)?;
+    Ok(())
+}
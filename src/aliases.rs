@@ -1,6 +1,8 @@
 use crate::{
-    aliases_patch::AliasesPatch, prelude::LanguageStrings, EntityId, FromJson, HeaderInfo,
-    LanguageString, RestApi, RestApiError, RevisionMatch,
+    aliases_patch::AliasesPatch,
+    endpoints::{Endpoint, GetItemAliases, GetPropertyAliases},
+    prelude::LanguageStrings,
+    EntityId, FromJson, HeaderInfo, LanguageString, RestApi, RestApiError, RevisionMatch,
 };
 use derive_where::DeriveWhere;
 use reqwest::StatusCode;
@@ -98,7 +100,10 @@ impl Aliases {
         api: &RestApi,
         rm: RevisionMatch,
     ) -> Result<reqwest::Response, RestApiError> {
-        let path = format!("/entities/{group}/{id}/aliases", group = id.group()?);
+        let path = match id {
+            EntityId::Property(_) => GetPropertyAliases { property_id: id }.path()?,
+            _ => GetItemAliases { item_id: id }.path()?,
+        };
         let mut request = api
             .wikibase_request_builder(&path, HashMap::new(), reqwest::Method::GET)
             .await?
@@ -1,4 +1,5 @@
 use crate::{
+    endpoints::{Endpoint, GetItemAliasesInLanguage, GetPropertyAliasesInLanguage},
     EditMetadata, EntityId, HeaderInfo, HttpGet, HttpMisc, Language, RestApi, RestApiError,
     RevisionMatch,
 };
@@ -122,6 +123,21 @@ impl AliasesInLanguage {
         &self.header_info
     }
 
+    fn rest_api_path(id: &EntityId, language: &str) -> Result<String, RestApiError> {
+        match id {
+            EntityId::Property(_) => GetPropertyAliasesInLanguage {
+                property_id: id,
+                language_code: language,
+            }
+            .path(),
+            _ => GetItemAliasesInLanguage {
+                item_id: id,
+                language_code: language,
+            }
+            .path(),
+        }
+    }
+
     async fn check_get_match_response(
         language: &str,
         response: Response,
@@ -141,11 +157,7 @@ impl AliasesInLanguage {
 
 impl HttpMisc for AliasesInLanguage {
     fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-        let language = Language::validated(&self.language)?;
-        Ok(format!(
-            "/entities/{group}/{id}/aliases/{language}",
-            group = id.group()?
-        ))
+        Self::rest_api_path(id, &self.language)
     }
 }
 
@@ -157,10 +169,7 @@ impl HttpGet for AliasesInLanguage {
         rm: RevisionMatch,
     ) -> Result<Self, RestApiError> {
         let language = Language::validated(language)?;
-        let path = format!(
-            "/entities/{group}/{id}/aliases/{language}",
-            group = id.group()?
-        );
+        let path = Self::rest_api_path(id, &language)?;
         let mut request = api
             .wikibase_request_builder(&path, HashMap::new(), reqwest::Method::GET)
             .await?
@@ -1,6 +1,8 @@
 use crate::{
-    aliases::Aliases, patch_entry::PatchEntry, EditMetadata, EntityId, FromJson, HttpMisc, Patch,
-    PatchApply, RestApi, RestApiError,
+    aliases::Aliases,
+    endpoints::{Endpoint, PatchItemAliases, PatchPropertyAliases},
+    patch_entry::PatchEntry,
+    EditMetadata, EntityId, FromJson, HttpMisc, Patch, PatchApply, RestApi, RestApiError,
 };
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -73,8 +75,11 @@ impl PatchApply<Aliases> for AliasesPatch {
 }
 
 impl HttpMisc for AliasesPatch {
-    fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!("/entities/{}/{id}/aliases", id.group()?))
+    fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
+        match id {
+            EntityId::Property(_) => PatchPropertyAliases { property_id: id }.path(),
+            _ => PatchItemAliases { item_id: id }.path(),
+        }
     }
 }
 
@@ -1,39 +1,244 @@
-use crate::{RestApi, RestApiError};
-use reqwest::Request;
+use crate::{
+    bot_password::{BotPassword, CookieSession},
+    secret::Secret,
+    token_store::{StoredTokens, TokenStore},
+    RestApi, RestApiError,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{header::HeaderMap, Request, StatusCode};
 use serde_json::Value;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-/// The default time to wait until bearer token is renewed. API says 4h so setting it to 3h50min
+/// The time to wait until bearer token is renewed, if the server does not say when it expires.
+/// API says 4h so setting it to 3h50min
 const DEFAULT_RENEWAL_INTERVAL_SEC: u64 = (3 * 60 + 50) * 60;
+/// An access token this close to its expiry is renewed before any request.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+/// A refresh token this close to its expiry is reported with `TokenEvent::RefreshTokenExpiring`.
+const REFRESH_TOKEN_WARNING: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Random bytes in a PKCE code verifier; encodes to 43 characters (RFC 7636 §4.1).
+const CODE_VERIFIER_BYTES: usize = 32;
+/// Random bytes in an authorization `state` value.
+const STATE_BYTES: usize = 16;
+/// Error codes with which a 401 response rejects an expired or revoked access token.
+const EXPIRED_TOKEN_CODES: [&str; 2] = ["invalid_token", "mwoauth-invalid-authorization"];
+
+/// A change in the lifecycle of the `OAuth2` tokens, passed to the callback set with
+/// `BearerToken::set_event_callback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenEvent {
+    /// An access token was obtained in exchange for an authorization code.
+    Obtained { expires_at: Option<SystemTime> },
+    /// The access token was renewed with the refresh token.
+    Renewed { expires_at: Option<SystemTime> },
+    /// Renewing the access token failed.
+    RenewalFailed { error: String },
+    /// The refresh token expires within a week; the user needs to authorize the client again
+    /// before then. Sent when the tokens are loaded from a `TokenStore`, obtained or renewed.
+    RefreshTokenExpiring { expires_at: SystemTime },
+}
+
+/// The callback for `TokenEvent`s.
+#[derive(Clone)]
+struct EventCallback(Arc<dyn Fn(&TokenEvent) + Send + Sync>);
+
+impl Debug for EventCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("EventCallback")
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct BearerToken {
     client_id: Option<String>,
-    client_secret: Option<String>,
-    access_token: Option<String>,
-    refresh_token: Option<String>,
+    client_secret: Option<Secret>,
+    public_client: bool,
+    redirect_uri: Option<String>,
+    scopes: Vec<String>,
+    code_verifier: Option<Secret>,
+    state: Option<Secret>,
+    access_token: Option<Secret>,
+    refresh_token: Option<Secret>,
     last_update: Option<std::time::Instant>,
     renewal_interval: std::time::Duration,
+    expires_at: Option<SystemTime>,
+    refresh_token_expires_at: Option<SystemTime>,
+    refresh_token_lifetime: Option<Duration>,
+    granted_scopes: Vec<String>,
+    event_callback: Option<EventCallback>,
+    store: Option<Arc<dyn TokenStore>>,
+    session: Option<CookieSession>,
 }
 
 impl BearerToken {
     /// Returns the `OAuth2` bearer token
-    pub const fn get(&self) -> &Option<String> {
+    pub const fn get(&self) -> &Option<Secret> {
         &self.access_token
     }
 
     /// For non-owner-only clients, returns a URL to send the user to login and authorize the client.
     /// Upon authorizing, the user will be redirected to the URL with a code, which can be exchanged for an access token, via `get_access_token`.
+    /// The redirect URI, scopes, and the PKCE challenge and `state` of a flow begun with
+    /// `start_authorization` are included if set.
     pub fn authorization_code_url(&self, api: &RestApi) -> Result<String, RestApiError> {
         let client_id = self
             .client_id
             .as_ref()
             .ok_or(RestApiError::ClientIdRequired)?;
-        Ok(format!(
-            "{}/oauth2/authorize?client_id={}&response_type=code",
-            api.api_url(),
-            client_id
-        ))
+        let url = format!("{}/oauth2/authorize", api.api_url());
+        let mut url =
+            reqwest::Url::parse(&url).map_err(|_| RestApiError::RestApiUrlInvalid(url))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("client_id", client_id)
+                .append_pair("response_type", "code");
+            if let Some(redirect_uri) = &self.redirect_uri {
+                query.append_pair("redirect_uri", redirect_uri);
+            }
+            if !self.scopes.is_empty() {
+                query.append_pair("scope", &self.scopes.join(" "));
+            }
+            if let Some(code_verifier) = &self.code_verifier {
+                query
+                    .append_pair(
+                        "code_challenge",
+                        &Self::code_challenge(code_verifier.expose()),
+                    )
+                    .append_pair("code_challenge_method", "S256");
+            }
+            if let Some(state) = &self.state {
+                query.append_pair("state", state.expose());
+            }
+        }
+        Ok(url.into())
+    }
+
+    /// Begins an authorization code flow with PKCE (RFC 7636): generates a fresh code verifier
+    /// and a random `state`, and returns the URL to send the user to. After the user has
+    /// authorized the client, pass the `code` and `state` from the redirect to
+    /// `complete_authorization`.
+    /// # Errors
+    /// Returns an error if the client ID is not set, or if no randomness is available.
+    pub fn start_authorization(&mut self, api: &RestApi) -> Result<String, RestApiError> {
+        self.code_verifier = Some(Self::random_string(CODE_VERIFIER_BYTES)?.into());
+        self.state = Some(Self::random_string(STATE_BYTES)?.into());
+        self.authorization_code_url(api)
+    }
+
+    /// Completes a flow begun with `start_authorization`: checks that `state` is the one that
+    /// was sent, then exchanges the code for an access token, proving possession of the code
+    /// verifier. The verifier and state are single-use and cleared either way.
+    /// # Errors
+    /// Returns `RestApiError::OAuth2StateMismatch` if `state` does not match (or no flow was
+    /// started), or an error if the token exchange fails.
+    pub async fn complete_authorization(
+        &mut self,
+        api: &RestApi,
+        code: &str,
+        state: &str,
+    ) -> Result<(), RestApiError> {
+        let expected = self.state.take();
+        if expected.as_ref().map(Secret::expose) != Some(state) {
+            self.code_verifier = None;
+            return Err(RestApiError::OAuth2StateMismatch);
+        }
+        let result = self.get_access_token(api, code).await;
+        self.code_verifier = None;
+        result
+    }
+
+    /// Returns the `state` of the authorization flow in progress, if any.
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_ref().map(Secret::expose)
+    }
+
+    /// Returns the redirect URI sent with the authorization and token requests.
+    pub fn redirect_uri(&self) -> Option<&str> {
+        self.redirect_uri.as_deref()
+    }
+
+    /// Returns the requested scopes.
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    /// Returns the scopes granted with the access token. If the server does not list them,
+    /// these are the requested scopes.
+    pub fn granted_scopes(&self) -> &[String] {
+        &self.granted_scopes
+    }
+
+    /// Returns when the access token expires, if the server said so.
+    pub const fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Returns when the refresh token expires, if the server said so or a lifetime was set
+    /// with `set_refresh_token_lifetime`.
+    pub const fn refresh_token_expires_at(&self) -> Option<SystemTime> {
+        self.refresh_token_expires_at
+    }
+
+    /// Sets how long a new refresh token is valid, for servers that do not say so in the
+    /// token response (`MediaWiki`'s default is a year).
+    pub const fn set_refresh_token_lifetime(&mut self, lifetime: Duration) {
+        self.refresh_token_lifetime = Some(lifetime);
+    }
+
+    /// Sets a callback that is called on each `TokenEvent`, e.g. to alert before the refresh
+    /// token expires. It runs while the `RestApi` holds the token's write lock, so it must not
+    /// use that `RestApi` or token (which would deadlock), and should return quickly; send the
+    /// event to a channel to do more.
+    pub fn set_event_callback<F: Fn(&TokenEvent) + Send + Sync + 'static>(&mut self, callback: F) {
+        self.event_callback = Some(EventCallback(Arc::new(callback)));
+    }
+
+    fn emit(&self, event: &TokenEvent) {
+        if let Some(EventCallback(callback)) = &self.event_callback {
+            callback(event);
+        }
+    }
+
+    /// Sends `TokenEvent::RefreshTokenExpiring` if the refresh token expires soon.
+    fn warn_if_refresh_token_expiring(&self) {
+        let Some(expires_at) = self.refresh_token_expires_at else {
+            return;
+        };
+        if Self::is_within(expires_at, REFRESH_TOKEN_WARNING) {
+            self.emit(&TokenEvent::RefreshTokenExpiring { expires_at });
+        }
+    }
+
+    /// Returns `true` if `time` is less than `margin` from now, or past.
+    fn is_within(time: SystemTime, margin: Duration) -> bool {
+        SystemTime::now()
+            .checked_add(margin)
+            .is_none_or(|deadline| deadline >= time)
+    }
+
+    /// Returns `true` for a public (non-confidential) client, which has no client secret.
+    pub const fn is_public_client(&self) -> bool {
+        self.public_client
+    }
+
+    /// The S256 code challenge for a code verifier.
+    fn code_challenge(code_verifier: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+    }
+
+    /// Returns `len` random bytes from the operating system, base64url-encoded.
+    fn random_string(len: usize) -> Result<String, RestApiError> {
+        let mut bytes = vec![0; len];
+        getrandom::fill(&mut bytes)
+            .map_err(|e| RestApiError::Randomness(std::io::Error::other(e.to_string())))?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
     }
 
     /// Returns the renewal interval for the `OAuth2` bearer token.
@@ -47,7 +252,7 @@ impl BearerToken {
     }
 
     /// Internal use only.
-    pub const fn client_secret(&self) -> &Option<String> {
+    pub const fn client_secret(&self) -> &Option<Secret> {
         &self.client_secret
     }
 
@@ -59,17 +264,40 @@ impl BearerToken {
             .client_id
             .as_ref()
             .ok_or(RestApiError::ClientIdRequired)?;
-        let client_secret = self
-            .client_secret
-            .as_ref()
-            .ok_or(RestApiError::ClientSecretRequired)?;
-
-        Ok(HashMap::from([
+        let mut params = HashMap::from([
             ("grant_type".to_string(), "authorization_code".to_string()),
             ("client_id".to_string(), client_id.clone()),
-            ("client_secret".to_string(), client_secret.clone()),
             ("code".to_string(), code.to_string()),
-        ]))
+        ]);
+        if let Some(client_secret) = self.client_secret_param()? {
+            params.insert("client_secret".to_string(), client_secret);
+        }
+        match &self.code_verifier {
+            Some(code_verifier) => {
+                params.insert(
+                    "code_verifier".to_string(),
+                    code_verifier.expose().to_string(),
+                );
+            }
+            // A public client can only prove itself with PKCE.
+            None if self.public_client => return Err(RestApiError::CodeVerifierRequired),
+            None => {}
+        }
+        if let Some(redirect_uri) = &self.redirect_uri {
+            params.insert("redirect_uri".to_string(), redirect_uri.clone());
+        }
+        Ok(params)
+    }
+
+    /// Returns the client secret to send, which a public client does not have.
+    fn client_secret_param(&self) -> Result<Option<String>, RestApiError> {
+        if self.public_client {
+            return Ok(None);
+        }
+        self.client_secret
+            .as_ref()
+            .map(|client_secret| Some(client_secret.expose().to_string()))
+            .ok_or(RestApiError::ClientSecretRequired)
     }
 
     async fn generate_get_access_token_request(
@@ -103,34 +331,105 @@ impl BearerToken {
         code: &str,
     ) -> Result<(), RestApiError> {
         let request = self.generate_get_access_token_request(api, code).await?;
-        let response = api.client().execute(request).await?;
+        let response = api.transport().execute(request).await?;
+        if !response.status().is_success() {
+            return Err(RestApiError::from_response(response).await);
+        }
         let j: Value = response.json().await?;
-        self.set_tokens_from_json(j)
+        self.set_tokens_from_json(j)?;
+        self.emit(&TokenEvent::Obtained {
+            expires_at: self.expires_at,
+        });
+        self.warn_if_refresh_token_expiring();
+        Ok(())
     }
 
-    /// Sets the `OAuth2` bearer token and refresh token from a JSON response
+    /// Sets the `OAuth2` bearer token and refresh token from a JSON response, with their
+    /// expiry and the granted scopes
     fn set_tokens_from_json(&mut self, j: Value) -> Result<(), RestApiError> {
         let access_token = j["access_token"]
             .as_str()
-            .ok_or(RestApiError::AccessTokenRequired)?
-            .to_string();
-        let refresh_token = j["refresh_token"]
-            .as_str()
-            .ok_or(RestApiError::RefreshTokenRequired)?
-            .to_string();
-        let renewal_interval = j["expires_in"].as_u64().unwrap_or_default() / 10 * 9; // 90% of max duration
-        self.set_tokens(Some(access_token), Some(refresh_token));
+            .ok_or(RestApiError::AccessTokenRequired)?;
+        let refresh_token = Secret::new(
+            j["refresh_token"]
+                .as_str()
+                .ok_or(RestApiError::RefreshTokenRequired)?,
+        );
+        let now = SystemTime::now();
+        let expires_in = j["expires_in"].as_u64();
+        self.expires_at = expires_in.and_then(|secs| now.checked_add(Duration::from_secs(secs)));
+        self.refresh_token_expires_at = match j["refresh_token_expires_in"].as_u64() {
+            Some(secs) => now.checked_add(Duration::from_secs(secs)),
+            // A refresh token that was not rotated keeps its expiry.
+            None if self.refresh_token.as_ref() == Some(&refresh_token) => {
+                self.refresh_token_expires_at
+            }
+            None => self
+                .refresh_token_lifetime
+                .and_then(|lifetime| now.checked_add(lifetime)),
+        };
+        self.granted_scopes = match j["scope"].as_str() {
+            Some(scope) => scope.split_whitespace().map(str::to_string).collect(),
+            // Without a `scope`, the requested scopes were granted (RFC 6749 §5.1).
+            None => self.scopes.clone(),
+        };
+        let renewal_interval = expires_in.unwrap_or_default() / 10 * 9; // 90% of max duration
+        self.access_token = Some(Secret::new(access_token));
+        self.refresh_token = Some(refresh_token);
         self.set_renewal_interval(renewal_interval);
         self.touch_access_token();
+        self.save_tokens()
+    }
+
+    /// Saves the tokens to the token store, if there is one.
+    fn save_tokens(&self) -> Result<(), RestApiError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let tokens = StoredTokens::new(
+            self.access_token.clone(),
+            self.refresh_token.clone(),
+            Some(self.renewal_interval),
+        )
+        .with_expiry(self.expires_at, self.refresh_token_expires_at)
+        .with_granted_scopes(self.granted_scopes.clone());
+        store.save(&tokens)
+    }
+
+    /// Sets the token store, and loads the tokens it holds. From now on, tokens are saved to
+    /// it whenever they are obtained or renewed.
+    /// # Errors
+    /// Returns an error if the store cannot be read.
+    pub fn set_token_store(&mut self, store: Arc<dyn TokenStore>) -> Result<(), RestApiError> {
+        if let Some(tokens) = store.load()? {
+            self.access_token = tokens.access_token().cloned();
+            self.refresh_token = tokens.refresh_token().cloned();
+            self.expires_at = tokens.expires_at();
+            self.refresh_token_expires_at = tokens.refresh_token_expires_at();
+            self.granted_scopes = tokens.granted_scopes().to_vec();
+            // Without a known renewal time, the access token is renewed before the first write.
+            self.last_update = None;
+            if let Some(renew_in) = tokens.renew_in() {
+                self.renewal_interval = renew_in;
+                self.touch_access_token();
+            }
+        }
+        self.store = Some(store);
+        self.warn_if_refresh_token_expiring();
         Ok(())
     }
 
+    /// Returns the token store, if any.
+    pub fn token_store(&self) -> Option<&Arc<dyn TokenStore>> {
+        self.store.as_ref()
+    }
+
     /// Updates the last bearer token update time to current time
     fn touch_access_token(&mut self) {
         self.last_update = Some(std::time::Instant::now());
     }
 
-    pub const fn refresh_token(&self) -> &Option<String> {
+    pub const fn refresh_token(&self) -> &Option<Secret> {
         &self.refresh_token
     }
 
@@ -143,10 +442,39 @@ impl BearerToken {
         self.renewal_interval = std::time::Duration::from_secs(renewal_interval);
     }
 
-    /// Sets the `OAuth2` bearer token and refresh token
+    /// Returns a token for another user of the same `OAuth2` client: the client ID, secret,
+    /// redirect URI, scopes, renewal interval and refresh token lifetime are kept, the tokens
+    /// replaced. A flow in progress, the token store, the event callback and a login session
+    /// are not carried over. The access token is taken to be fresh; once the server rejects
+    /// it as expired, it is renewed with the refresh token.
+    pub fn for_user(&self, access_token: Option<String>, refresh_token: Option<String>) -> Self {
+        let renewal_interval = match self.renewal_interval {
+            interval if interval.is_zero() => {
+                std::time::Duration::from_secs(DEFAULT_RENEWAL_INTERVAL_SEC)
+            }
+            interval => interval,
+        };
+        Self {
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+            public_client: self.public_client,
+            redirect_uri: self.redirect_uri.clone(),
+            scopes: self.scopes.clone(),
+            access_token: access_token.map(Secret::from),
+            refresh_token: refresh_token.map(Secret::from),
+            last_update: Some(std::time::Instant::now()),
+            renewal_interval,
+            refresh_token_lifetime: self.refresh_token_lifetime,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the `OAuth2` bearer token and refresh token, whose expiry is then unknown
     pub fn set_tokens(&mut self, access_token: Option<String>, refresh_token: Option<String>) {
-        self.access_token = access_token;
-        self.refresh_token = refresh_token;
+        self.access_token = access_token.map(Secret::from);
+        self.refresh_token = refresh_token.map(Secret::from);
+        self.expires_at = None;
+        self.refresh_token_expires_at = None;
     }
 
     /// Checks if the bearer token needs to be updated, and updates it if necessary
@@ -158,16 +486,21 @@ impl BearerToken {
     }
 
     /// Returns `true` if a request with this method requires an up-front token renewal.
-    /// Read-only, so the hot path (GETs and fresh tokens) needs only a read lock.
+    /// Read-only, so the hot path (fresh tokens) needs only a read lock.
+    ///
+    /// GETs work without a token, so they only renew a token whose expiry is known and that
+    /// can be renewed; a token of unknown age is renewed before the next write.
     pub(crate) fn needs_renewal(&self, method: &reqwest::Method) -> bool {
+        if !self.can_update_access_token() || !self.does_access_token_need_updating() {
+            return false;
+        }
         *method != reqwest::Method::GET
-            && self.can_update_access_token()
-            && self.does_access_token_need_updating()
+            || (self.expires_at.is_some() && self.refresh_token.is_some())
     }
 
     /// Sets the `OAuth2` bearer token (owner-only clients are supported)
     pub fn set_access_token<S: Into<String>>(&mut self, access_token: S) {
-        self.access_token = Some(access_token.into());
+        self.access_token = Some(Secret::new(access_token));
     }
 
     //// Sets the OAuth2 client ID and client secret
@@ -177,7 +510,84 @@ impl BearerToken {
         client_secret: S2,
     ) {
         self.client_id = Some(client_id.into());
-        self.client_secret = Some(client_secret.into());
+        self.client_secret = Some(Secret::new(client_secret));
+        self.public_client = false;
+    }
+
+    /// Sets the `OAuth2` client ID of a public (non-confidential) client, such as a desktop
+    /// app, which has no client secret. Such a client must use `start_authorization` (PKCE)
+    /// to get an access token.
+    pub fn set_oauth2_public_client<S: Into<String>>(&mut self, client_id: S) {
+        self.client_id = Some(client_id.into());
+        self.client_secret = None;
+        self.public_client = true;
+    }
+
+    /// Sets the redirect URI, which must match the one registered for the client.
+    pub fn set_redirect_uri<S: Into<String>>(&mut self, redirect_uri: S) {
+        self.redirect_uri = Some(redirect_uri.into());
+    }
+
+    /// Sets the scopes to request, e.g. `["basic", "editpage"]`.
+    pub fn set_scopes<I, S>(&mut self, scopes: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+    }
+
+    /// Sets a bot password to log in with through the Action API, for wikis without `OAuth`.
+    /// Requests are then authorized by the session cookies; see `login`.
+    pub fn set_bot_password<S1: Into<String>, S2: Into<String>>(
+        &mut self,
+        username: S1,
+        password: S2,
+    ) {
+        self.session = Some(CookieSession::new(BotPassword::new(username, password)));
+    }
+
+    /// Returns the login session, if a bot password is set.
+    pub const fn session(&self) -> Option<&CookieSession> {
+        self.session.as_ref()
+    }
+
+    /// Logs in with the bot password, replacing any previous session. `RestApi` does this
+    /// before the first request by itself; call it again if the session has expired, or to
+    /// retry after the wiki rejected the bot password.
+    /// # Errors
+    /// Returns `RestApiError::BotPasswordRequired` if no bot password is set,
+    /// `RestApiError::LoginFailed` if the wiki rejects it, or an error if a request fails.
+    pub async fn login(&mut self, api: &RestApi) -> Result<(), RestApiError> {
+        let credentials = self
+            .session
+            .as_ref()
+            .ok_or(RestApiError::BotPasswordRequired)?
+            .credentials()
+            .clone();
+        let mut session = CookieSession::new(credentials);
+        let result = session.login(api).await;
+        self.session = Some(session);
+        result
+    }
+
+    /// Returns `true` if a bot password is set but not logged in yet, and the wiki has not
+    /// rejected it.
+    pub(crate) fn needs_login(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| !session.is_logged_in() && session.rejection().is_none())
+    }
+
+    /// Fails with `RestApiError::LoginFailed` if the wiki rejected the bot password, so the
+    /// login is not repeated for every request.
+    pub(crate) fn check_login_rejection(&self) -> Result<(), RestApiError> {
+        self.session
+            .as_ref()
+            .and_then(CookieSession::rejection)
+            .map_or(Ok(()), |reason| {
+                Err(RestApiError::LoginFailed(reason.to_string()))
+            })
     }
 
     /// Returns `true` if an `OAuth2` bearer token is present
@@ -185,13 +595,51 @@ impl BearerToken {
         self.access_token.is_some()
     }
 
-    /// Returns `true` if the client ID and client secret are present
+    /// Returns `true` if the access token can be renewed with a refresh token.
+    pub(crate) const fn can_renew(&self) -> bool {
+        self.can_update_access_token() && self.refresh_token.is_some()
+    }
+
+    /// Returns `true` if a response rejects the access token as expired or revoked: a 401
+    /// with an `invalid_token` challenge (RFC 6750) or error code.
+    pub(crate) fn is_expired_token_rejection(
+        status: StatusCode,
+        headers: &HeaderMap,
+        error_code: Option<&str>,
+    ) -> bool {
+        if status != StatusCode::UNAUTHORIZED {
+            return false;
+        }
+        let challenged = headers
+            .get_all(reqwest::header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("invalid_token"));
+        challenged || error_code.is_some_and(|code| EXPIRED_TOKEN_CODES.contains(&code))
+    }
+
+    /// Renews the access token after the server rejected it, regardless of its age.
+    pub(crate) async fn renew_rejected_access_token(
+        &mut self,
+        api: &RestApi,
+    ) -> Result<(), RestApiError> {
+        self.request_renewal(api).await
+    }
+
+    /// Returns `true` if the client ID and client secret are present, or the client is public
     const fn can_update_access_token(&self) -> bool {
-        self.client_id.is_some() && self.client_secret.is_some()
+        self.client_id.is_some() && (self.client_secret.is_some() || self.public_client)
     }
 
-    /// Check if last bearer token update is within the renewal interval
+    /// Check if last bearer token update is within the renewal interval, and the access token
+    /// is not about to expire
     fn does_access_token_need_updating(&self) -> bool {
+        if self
+            .expires_at
+            .is_some_and(|expires_at| Self::is_within(expires_at, EXPIRY_MARGIN))
+        {
+            return true;
+        }
         if let Some(last_update) = self.last_update {
             let elapsed = last_update.elapsed();
             if elapsed < self.renewal_interval {
@@ -206,20 +654,23 @@ impl BearerToken {
             .client_id
             .as_ref()
             .ok_or(RestApiError::ClientIdRequired)?;
-        let client_secret = self
-            .client_secret
-            .as_ref()
-            .ok_or(RestApiError::ClientSecretRequired)?;
+        let client_secret = self.client_secret_param()?;
         let refresh_token = self
             .refresh_token
             .as_ref()
             .ok_or(RestApiError::RefreshTokenRequired)?;
-        Ok(HashMap::from([
+        let mut params = HashMap::from([
             ("client_id".to_string(), client_id.clone()),
-            ("client_secret".to_string(), client_secret.clone()),
             ("grant_type".to_string(), "refresh_token".to_string()),
-            ("refresh_token".to_string(), refresh_token.clone()),
-        ]))
+            (
+                "refresh_token".to_string(),
+                refresh_token.expose().to_string(),
+            ),
+        ]);
+        if let Some(client_secret) = client_secret {
+            params.insert("client_secret".to_string(), client_secret);
+        }
+        Ok(params)
     }
 
     async fn get_renew_access_token_request(&self, api: &RestApi) -> Result<Request, RestApiError> {
@@ -245,8 +696,31 @@ impl BearerToken {
         if !self.does_access_token_need_updating() {
             return Ok(());
         }
+        self.request_renewal(api).await
+    }
+
+    /// Exchanges the refresh token for a new access token.
+    async fn request_renewal(&mut self, api: &RestApi) -> Result<(), RestApiError> {
+        if let Err(e) = self.exchange_refresh_token(api).await {
+            self.emit(&TokenEvent::RenewalFailed {
+                error: e.to_string(),
+            });
+            return Err(e);
+        }
+        api.stats.record_token_renewal();
+        self.emit(&TokenEvent::Renewed {
+            expires_at: self.expires_at,
+        });
+        self.warn_if_refresh_token_expiring();
+        Ok(())
+    }
+
+    async fn exchange_refresh_token(&mut self, api: &RestApi) -> Result<(), RestApiError> {
         let request = self.get_renew_access_token_request(api).await?;
-        let response = api.client().execute(request).await?;
+        let response = api.transport().execute(request).await?;
+        if !response.status().is_success() {
+            return Err(RestApiError::from_response(response).await);
+        }
         let j: Value = response.json().await?;
         self.set_tokens_from_json(j)
     }
@@ -290,7 +764,7 @@ mod tests {
         let mut token = BearerToken::default();
         assert_eq!(token.get(), &None);
         token.set_access_token("test");
-        assert_eq!(token.get(), &Some("test".to_string()));
+        assert_eq!(token.get(), &Some("test".into()));
     }
 
     #[test]
@@ -305,6 +779,118 @@ mod tests {
         assert_eq!(token.authorization_code_url(&api).unwrap(), "https://www.wikidata.org/w/rest.php/oauth2/authorize?client_id=client_id&response_type=code");
     }
 
+    #[test]
+    fn test_code_challenge() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            BearerToken::code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_start_authorization() {
+        let api = RestApi::builder("https://www.wikidata.org/w/rest.php")
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut token = BearerToken::default();
+        token.set_oauth2_public_client("client_id");
+        token.set_redirect_uri("http://127.0.0.1:8080/callback");
+        token.set_scopes(["basic", "editpage"]);
+        let url = reqwest::Url::parse(&token.start_authorization(&api).unwrap()).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], "client_id");
+        assert_eq!(query["redirect_uri"], "http://127.0.0.1:8080/callback");
+        assert_eq!(query["scope"], "basic editpage");
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(query["state"], token.state().unwrap());
+        let verifier = token.code_verifier.as_ref().unwrap().expose().to_string();
+        assert_eq!(verifier.len(), 43);
+        assert_eq!(
+            query["code_challenge"],
+            BearerToken::code_challenge(&verifier)
+        );
+        let debug = format!("{token:?}");
+        assert!(!debug.contains(&verifier));
+        assert!(!debug.contains(token.state().unwrap()));
+
+        // Every flow gets a fresh verifier and state.
+        let state = token.state().unwrap().to_string();
+        token.start_authorization(&api).unwrap();
+        assert_ne!(token.state().unwrap(), state);
+        assert_ne!(token.code_verifier.as_ref().unwrap().expose(), verifier);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_complete_authorization() {
+        let mock_server = MockServer::start().await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_oauth2_public_client("desktop_tool")
+            .with_oauth2_redirect_uri("http://127.0.0.1:8080/callback")
+            .build()
+            .unwrap();
+        let token = api.token();
+        let mut token = token.write().await;
+
+        // Without a verifier, a public client cannot exchange a code.
+        assert!(matches!(
+            token.get_access_token(&api, "code").await,
+            Err(RestApiError::CodeVerifierRequired)
+        ));
+
+        // A forged state is rejected without a request, and ends the flow.
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        token.start_authorization(&api).unwrap();
+        let first_state = token.state().unwrap().to_string();
+        let forged = token.complete_authorization(&api, "code", "forged").await;
+        assert!(matches!(forged, Err(RestApiError::OAuth2StateMismatch)));
+        let replayed = token
+            .complete_authorization(&api, "code", &first_state)
+            .await;
+        assert!(matches!(replayed, Err(RestApiError::OAuth2StateMismatch)));
+        mock_server.verify().await;
+        mock_server.reset().await;
+
+        token.start_authorization(&api).unwrap();
+        let verifier = token.code_verifier.as_ref().unwrap().expose().to_string();
+        let state = token.state().unwrap().to_string();
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("client_id=desktop_tool"))
+            .and(body_string_contains(format!("code_verifier={verifier}")))
+            .and(body_string_contains("redirect_uri=http"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "access_token_pkce",
+                "refresh_token": "refresh_token_pkce",
+                "expires_in": 3600,
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        token
+            .complete_authorization(&api, "code", &state)
+            .await
+            .unwrap();
+        assert_eq!(token.get(), &Some("access_token_pkce".into()));
+        assert!(token.state().is_none());
+        assert!(token.code_verifier.is_none());
+
+        // Renewal works without a client secret.
+        let params = token.get_renew_access_token_parameters().unwrap();
+        assert!(!params.contains_key("client_secret"));
+        assert!(token.can_update_access_token());
+        mock_server.verify().await;
+    }
+
     #[test]
     fn test_set_tokens_from_json() {
         let mut token = BearerToken::default();
@@ -314,12 +900,67 @@ mod tests {
             "expires_in": 3600,
         });
         token.set_tokens_from_json(j).unwrap();
-        assert_eq!(token.get(), &Some("foo".to_string()));
-        assert_eq!(token.refresh_token(), &Some("bar".to_string()));
+        assert_eq!(token.get(), &Some("foo".into()));
+        assert_eq!(token.refresh_token(), &Some("bar".into()));
         assert_eq!(
             token.renewal_interval,
             std::time::Duration::from_secs(3600 / 10 * 9)
         );
+        let expires_in = token
+            .expires_at()
+            .unwrap()
+            .duration_since(SystemTime::now())
+            .unwrap();
+        assert!(expires_in > Duration::from_secs(3590) && expires_in <= Duration::from_secs(3600));
+        assert_eq!(token.refresh_token_expires_at(), None);
+        assert!(token.granted_scopes().is_empty());
+    }
+
+    #[test]
+    fn test_set_tokens_from_json_expiry_and_scopes() {
+        let mut token = BearerToken::default();
+        token.set_scopes(["basic", "editpage", "createeditmovepage"]);
+        token.set_refresh_token_lifetime(Duration::from_secs(86400));
+        token
+            .set_tokens_from_json(json!({"access_token": "a1", "refresh_token": "r1"}))
+            .unwrap();
+        // Without `expires_in`, the expiry is unknown and the default interval applies.
+        assert_eq!(token.expires_at(), None);
+        assert_eq!(
+            token.renewal_interval,
+            Duration::from_secs(DEFAULT_RENEWAL_INTERVAL_SEC)
+        );
+        assert_eq!(token.granted_scopes(), token.scopes());
+        let refresh_expiry = token.refresh_token_expires_at().unwrap();
+        assert!(refresh_expiry > SystemTime::now() + Duration::from_secs(86000));
+
+        // The same refresh token keeps its expiry; the server may grant fewer scopes.
+        token
+            .set_tokens_from_json(json!({
+                "access_token": "a2",
+                "refresh_token": "r1",
+                "expires_in": 14400,
+                "scope": "basic editpage",
+            }))
+            .unwrap();
+        assert_eq!(token.refresh_token_expires_at(), Some(refresh_expiry));
+        assert_eq!(token.granted_scopes(), ["basic", "editpage"]);
+
+        // A stated refresh token lifetime wins over the configured one.
+        token
+            .set_tokens_from_json(json!({
+                "access_token": "a3",
+                "refresh_token": "r2",
+                "refresh_token_expires_in": 60,
+            }))
+            .unwrap();
+        assert!(
+            token.refresh_token_expires_at().unwrap() < SystemTime::now() + Duration::from_secs(61)
+        );
+
+        token.set_tokens(Some("a4".into()), Some("r3".into()));
+        assert_eq!(token.expires_at(), None);
+        assert_eq!(token.refresh_token_expires_at(), None);
     }
 
     #[tokio::test]
@@ -374,11 +1015,11 @@ mod tests {
             .unwrap();
         assert_eq!(
             api.token.read().await.get().to_owned().unwrap(),
-            "access_token_foobar"
+            Secret::from("access_token_foobar")
         );
         assert_eq!(
             api.token.read().await.refresh_token().to_owned().unwrap(),
-            "refresh_token_foobar"
+            Secret::from("refresh_token_foobar")
         );
         assert_eq!(
             api.token.read().await.renewal_interval,
@@ -435,7 +1076,7 @@ mod tests {
         api.token
             .write()
             .await
-            .set_tokens(None, Some("refresh_token_foobar".to_string()));
+            .set_tokens(None, Some("refresh_token_foobar".into()));
         api.token
             .write()
             .await
@@ -444,11 +1085,11 @@ mod tests {
             .unwrap();
         assert_eq!(
             api.token.read().await.get().to_owned().unwrap(),
-            "access_token_foobar2"
+            Secret::from("access_token_foobar2")
         );
         assert_eq!(
             api.token.read().await.refresh_token().to_owned().unwrap(),
-            "refresh_token_foobar2"
+            Secret::from("refresh_token_foobar2")
         );
         assert_eq!(
             api.token.read().await.renewal_interval,
@@ -487,7 +1128,7 @@ mod tests {
     fn test_get_renew_access_token_parameters() {
         let mut token = BearerToken::default();
         token.set_oauth2_info("test_client", "test_secret");
-        token.set_tokens(None, Some("test_refresh".to_string()));
+        token.set_tokens(None, Some("test_refresh".into()));
         let params = token.get_renew_access_token_parameters().unwrap();
         assert_eq!(params.get("grant_type").unwrap(), "refresh_token");
         assert_eq!(params.get("client_id").unwrap(), "test_client");
@@ -499,7 +1140,7 @@ mod tests {
     fn test_generate_get_access_token_parameters_missing_secret() {
         // client_id present but client_secret missing -> ClientSecretRequired.
         let token = BearerToken {
-            client_id: Some("id".to_string()),
+            client_id: Some("id".into()),
             ..Default::default()
         };
         assert!(matches!(
@@ -511,7 +1152,7 @@ mod tests {
     #[test]
     fn test_get_renew_access_token_parameters_missing_secret() {
         let token = BearerToken {
-            client_id: Some("id".to_string()),
+            client_id: Some("id".into()),
             ..Default::default()
         };
         assert!(matches!(
@@ -552,6 +1193,17 @@ mod tests {
         assert!(!token.needs_renewal(&reqwest::Method::GET));
         // A non-GET with OAuth2 info and no prior update needs a renewal.
         assert!(token.needs_renewal(&reqwest::Method::POST));
+
+        // A token that is about to expire is renewed before any request.
+        token.set_tokens(Some("access".into()), Some("refresh".into()));
+        token.touch_access_token();
+        token.set_renewal_interval(3600);
+        token.expires_at = Some(SystemTime::now() + Duration::from_secs(3600));
+        assert!(!token.needs_renewal(&reqwest::Method::GET));
+        assert!(!token.needs_renewal(&reqwest::Method::POST));
+        token.expires_at = Some(SystemTime::now() + Duration::from_secs(30));
+        assert!(token.needs_renewal(&reqwest::Method::GET));
+        assert!(token.needs_renewal(&reqwest::Method::POST));
     }
 
     #[tokio::test]
@@ -574,7 +1226,7 @@ mod tests {
 
         let mut token = BearerToken::default();
         token.set_oauth2_info("id", "secret");
-        token.set_tokens(None, Some("refresh".to_string()));
+        token.set_tokens(None, Some("refresh".into()));
 
         // A GET request: needs_renewal is false, so check() is a no-op.
         let get_req = Request::new(reqwest::Method::GET, api.api_url().parse().unwrap());
@@ -584,6 +1236,79 @@ mod tests {
         // A non-GET request triggers renewal via the mocked token endpoint.
         let post_req = Request::new(reqwest::Method::POST, api.api_url().parse().unwrap());
         token.check(&api, &post_req).await.unwrap();
-        assert_eq!(token.get(), &Some("new_access".to_string()));
+        assert_eq!(token.get(), &Some("new_access".into()));
+
+        // Once the expiry is known, a GET renews a token that is about to expire.
+        token.set_access_token("expiring_access");
+        token.expires_at = Some(SystemTime::now());
+        token.check(&api, &get_req).await.unwrap();
+        assert_eq!(token.get(), &Some("new_access".into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_token_events() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .and(body_string_contains("refresh_token=good_refresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "new_access",
+                "refresh_token": "good_refresh",
+                "expires_in": 14400,
+                "refresh_token_expires_in": 2 * 24 * 60 * 60,
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "invalid_grant",
+                "message": "The refresh token is invalid.",
+            })))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap();
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut token = BearerToken::default();
+        token.set_oauth2_info("id", "secret");
+        let recorded = events.clone();
+        token.set_event_callback(move |event| recorded.lock().unwrap().push(event.clone()));
+
+        token.set_tokens(None, Some("good_refresh".into()));
+        token.renew_access_token(&api).await.unwrap();
+        let expires_at = token.expires_at().unwrap();
+        let refresh_expires_at = token.refresh_token_expires_at().unwrap();
+        assert_eq!(
+            events.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            vec![
+                TokenEvent::Renewed {
+                    expires_at: Some(expires_at)
+                },
+                TokenEvent::RefreshTokenExpiring {
+                    expires_at: refresh_expires_at
+                },
+            ]
+        );
+
+        token.set_tokens(None, Some("revoked_refresh".into()));
+        match token.renew_rejected_access_token(&api).await {
+            Err(RestApiError::ApiError {
+                status, payload, ..
+            }) => {
+                assert_eq!(status, 400);
+                assert_eq!(payload.code(), "invalid_grant");
+                assert_eq!(payload.message(), "The refresh token is invalid.");
+            }
+            other => panic!("Wrong result: {other:?}"),
+        }
+        let failed = events.lock().unwrap().clone();
+        match failed.as_slice() {
+            [TokenEvent::RenewalFailed { error }] => assert!(error.contains("invalid_grant")),
+            other => panic!("Wrong events: {other:?}"),
+        }
     }
 }
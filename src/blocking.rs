@@ -0,0 +1,252 @@
+//! A blocking facade over the async API, for synchronous programs.
+//!
+//! `blocking::RestApi` wraps a `crate::RestApi` together with its own single-threaded tokio
+//! runtime, like `reqwest::blocking` does, and runs each operation to completion. Its methods
+//! must not be called from within an async runtime; use the async API there.
+//!
+//! ```no_run
+//! use wikibase_rest_api::prelude::*;
+//!
+//! let api = wikibase_rest_api::blocking::RestApi::wikidata().unwrap();
+//! let item: Item = api.get_entity(EntityId::item("Q42")).unwrap();
+//! ```
+
+use crate::{
+    bearer_token::BearerToken,
+    entity::{Entity, EntityType},
+    entity_patch::EntityPatch,
+    get_put_delete::{HttpDelete, HttpGet, HttpGetEntity, HttpPut},
+    patch::{FromJson, PatchApply},
+    search::{Search, SearchResult},
+    EditMetadata, EntityContainer, EntityId, Item, LoadReport, Property, RestApiError,
+    RevisionMatch,
+};
+use std::{future::Future, sync::Arc};
+
+/// A blocking `RestApi`. Clones share the underlying `RestApi` and runtime.
+#[derive(Debug, Clone)]
+pub struct RestApi {
+    api: crate::RestApi,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+
+impl RestApi {
+    /// Wraps an async `RestApi`, e.g. one from `crate::RestApi::builder(...).build()`.
+    /// # Errors
+    /// Returns an error if the runtime cannot be started
+    pub fn new(api: crate::RestApi) -> Result<Self, RestApiError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(RestApiError::Runtime)?;
+        Ok(Self {
+            api,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Returns a blocking `RestApi` for Wikidata
+    pub fn wikidata() -> Result<Self, RestApiError> {
+        Self::new(crate::RestApi::wikidata()?)
+    }
+
+    /// Returns the async `RestApi`, e.g. to build an `EntityContainer`
+    pub const fn api(&self) -> &crate::RestApi {
+        &self.api
+    }
+
+    /// Blocking version of `crate::RestApi::as_user`; the runtime is shared too.
+    pub fn as_user(&self, token: BearerToken) -> Self {
+        Self {
+            api: self.api.as_user(token),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Runs any future on the internal runtime, for operations without a blocking wrapper.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Blocking version of `Entity::get`
+    pub fn get_entity<E: Entity>(&self, id: EntityId) -> Result<E, RestApiError> {
+        self.block_on(E::get(id, &self.api))
+    }
+
+    /// Blocking version of `Entity::get_match`
+    pub fn get_entity_match<E: Entity>(
+        &self,
+        id: EntityId,
+        rm: RevisionMatch,
+    ) -> Result<E, RestApiError> {
+        self.block_on(E::get_match(id, &self.api, rm))
+    }
+
+    /// Blocking version of `Entity::post`
+    pub fn post_entity<E: Entity>(&self, entity: &E) -> Result<E, RestApiError> {
+        self.block_on(entity.post(&self.api))
+    }
+
+    /// Blocking version of `Entity::post_with_type_and_metadata`
+    pub fn post_entity_meta<E: Entity>(
+        &self,
+        entity: &E,
+        entity_type: EntityType,
+        em: EditMetadata,
+    ) -> Result<E, RestApiError> {
+        self.block_on(entity.post_with_type_and_metadata(entity_type, &self.api, em))
+    }
+
+    /// Blocking version of `EntityPatch::apply_match_item`
+    pub fn patch_item(
+        &self,
+        patch: &EntityPatch,
+        id: &EntityId,
+        em: EditMetadata,
+    ) -> Result<Item, RestApiError> {
+        self.block_on(patch.apply_match_item(id, &self.api, em))
+    }
+
+    /// Blocking version of `EntityPatch::apply_match_property`
+    pub fn patch_property(
+        &self,
+        patch: &EntityPatch,
+        id: &EntityId,
+        em: EditMetadata,
+    ) -> Result<Property, RestApiError> {
+        self.block_on(patch.apply_match_property(id, &self.api, em))
+    }
+
+    /// Blocking version of `PatchApply::apply_match`, for patches of parts of an entity
+    pub fn apply_patch<T: FromJson, P: PatchApply<T>>(
+        &self,
+        patch: &P,
+        id: &EntityId,
+        em: EditMetadata,
+    ) -> Result<T, RestApiError> {
+        self.block_on(patch.apply_match(id, &self.api, em))
+    }
+
+    /// Blocking version of `HttpGet::get`
+    pub fn get<T: HttpGet>(&self, id: &EntityId, part_id: &str) -> Result<T, RestApiError> {
+        self.block_on(T::get(id, part_id, &self.api))
+    }
+
+    /// Blocking version of `HttpGet::get_match`
+    pub fn get_match<T: HttpGet>(
+        &self,
+        id: &EntityId,
+        part_id: &str,
+        rm: RevisionMatch,
+    ) -> Result<T, RestApiError> {
+        self.block_on(T::get_match(id, part_id, &self.api, rm))
+    }
+
+    /// Blocking version of `HttpGetEntity::get`, e.g. for all labels of an entity
+    pub fn get_part<T: HttpGetEntity>(&self, id: &EntityId) -> Result<T, RestApiError> {
+        self.block_on(T::get(id, &self.api))
+    }
+
+    /// Blocking version of `HttpPut::put_meta`
+    pub fn put<T: HttpPut>(
+        &self,
+        value: &T,
+        id: &EntityId,
+        em: EditMetadata,
+    ) -> Result<T, RestApiError> {
+        self.block_on(value.put_meta(id, &self.api, em))
+    }
+
+    /// Blocking version of `HttpDelete::delete_meta`
+    pub fn delete<T: HttpDelete>(
+        &self,
+        value: &T,
+        id: &EntityId,
+        em: EditMetadata,
+    ) -> Result<(), RestApiError> {
+        self.block_on(value.delete_meta(id, &self.api, em))
+    }
+
+    /// Blocking version of `Search::get`
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchResult>, RestApiError> {
+        self.block_on(search.get(&self.api))
+    }
+
+    /// Blocking version of `EntityContainer::load`
+    pub fn load(
+        &self,
+        container: &EntityContainer,
+        entity_ids: &[EntityId],
+    ) -> Result<(), RestApiError> {
+        self.block_on(container.load(entity_ids))
+    }
+
+    /// Blocking version of `EntityContainer::load_report`
+    pub fn load_report(&self, container: &EntityContainer, entity_ids: &[EntityId]) -> LoadReport {
+        self.block_on(container.load_report(entity_ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{label::Label, Language};
+    use serde_json::{json, Value};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Starts a mock server on its own runtime, which keeps serving in the background.
+    fn mock_server(runtime: &tokio::runtime::Runtime) -> MockServer {
+        let v: Value =
+            serde_json::from_str(&std::fs::read_to_string("test_data/Q42.json").unwrap()).unwrap();
+        runtime.block_on(async {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(&v))
+                .mount(&mock_server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/w/rest.php/wikibase/v1/entities/items/Q42/labels/en"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!("Douglas Adams")))
+                .mount(&mock_server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path("/w/rest.php/wikibase/v1/search/items"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({"results": []})))
+                .mount(&mock_server)
+                .await;
+            mock_server
+        })
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_blocking_api() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mock_server = mock_server(&runtime);
+        let api = RestApi::new(
+            crate::RestApi::builder(mock_server.uri() + "/w/rest.php")
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let item: Item = api.get_entity(EntityId::item("Q42")).unwrap();
+        assert_eq!(item.id(), &EntityId::item("Q42"));
+
+        let label: Label = api.get(&EntityId::item("Q42"), "en").unwrap();
+        assert_eq!(label.value(), "Douglas Adams");
+
+        let search = Search::items("Douglas Adams", Language::try_new("en").unwrap());
+        assert!(api.search(&search).unwrap().is_empty());
+
+        let container = EntityContainer::builder()
+            .api(Arc::new(api.api().clone()))
+            .build()
+            .unwrap();
+        api.load(&container, &[EntityId::item("Q42")]).unwrap();
+        assert!(api.block_on(container.get_item("Q42")).is_some());
+    }
+}
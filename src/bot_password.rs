@@ -0,0 +1,379 @@
+//! Session-cookie login through the `MediaWiki` Action API, for wikis without the `OAuth`
+//! extension.
+//!
+//! Create a bot password on `Special:BotPasswords` and set it with
+//! `RestApiBuilder::with_bot_password`. The `RestApi` logs in (`action=login`, after getting a
+//! login token) before its first request, and then sends the session cookies with every
+//! request, so writes are made as that user.
+//!
+//! If the wiki rejects the bot password, later requests fail with the same
+//! `RestApiError::LoginFailed` without trying again, until `RestApi::login` is called. The
+//! session cookies are never refreshed, and an expired session is not detected: the wiki
+//! then treats requests as anonymous. Call `RestApi::login` again to start a new session,
+//! e.g. when writes fail with a permission error.
+
+use crate::{secret::Secret, RestApi, RestApiError};
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    header::{HeaderValue, SET_COOKIE},
+    Url,
+};
+use serde_json::Value;
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+/// The credentials of a bot password: a user name of the form `User@BotName`, and the
+/// generated password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BotPassword {
+    username: String,
+    password: Secret,
+}
+
+impl BotPassword {
+    /// Creates bot password credentials.
+    pub fn new<S1: Into<String>, S2: Into<String>>(username: S1, password: S2) -> Self {
+        Self {
+            username: username.into(),
+            password: Secret::new(password),
+        }
+    }
+
+    /// Returns the user name, e.g. `ExampleUser@MyBot`.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// A login session: the bot password and the session cookies it obtained. Clones share the
+/// cookies, which `Debug` output does not show.
+#[derive(Clone)]
+pub struct CookieSession {
+    credentials: BotPassword,
+    jar: Arc<Jar>,
+    logged_in: bool,
+    rejection: Option<String>,
+}
+
+impl Debug for CookieSession {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieSession")
+            .field("credentials", &self.credentials)
+            .field("jar", &format_args!("[REDACTED]"))
+            .field("logged_in", &self.logged_in)
+            .field("rejection", &self.rejection)
+            .finish()
+    }
+}
+
+impl CookieSession {
+    /// Creates a session that is not logged in yet.
+    pub fn new(credentials: BotPassword) -> Self {
+        Self {
+            credentials,
+            jar: Arc::new(Jar::default()),
+            logged_in: false,
+            rejection: None,
+        }
+    }
+
+    /// Returns the credentials.
+    pub const fn credentials(&self) -> &BotPassword {
+        &self.credentials
+    }
+
+    /// Returns `true` once a login has succeeded.
+    pub const fn is_logged_in(&self) -> bool {
+        self.logged_in
+    }
+
+    /// Returns why the wiki rejected the last login, if it did.
+    pub fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
+
+    /// Returns the `Cookie` header to send to `url`, if there are cookies for it.
+    pub fn cookie_header(&self, url: &Url) -> Option<HeaderValue> {
+        self.jar.cookies(url)
+    }
+
+    /// Logs in with `action=login`: gets a login token, then posts the bot password with it.
+    /// The session cookies set along the way are kept for later requests.
+    /// # Errors
+    /// Returns `RestApiError::LoginFailed` if the wiki rejects the login, or an error if a
+    /// request fails.
+    pub async fn login(&mut self, api: &RestApi) -> Result<(), RestApiError> {
+        self.logged_in = false;
+        self.rejection = None;
+        let url = Self::action_api_url(api)?;
+        let token_query = [
+            ("action", "query"),
+            ("meta", "tokens"),
+            ("type", "login"),
+            ("format", "json"),
+        ];
+        let token_request = self.request(api, api.client().get(url.clone()).query(&token_query))?;
+        let tokens = self.send(api, &url, token_request).await?;
+        let login_token = tokens["query"]["tokens"]["logintoken"]
+            .as_str()
+            .ok_or_else(|| RestApiError::LoginFailed("no login token".to_string()))?;
+
+        let form = [
+            ("action", "login"),
+            ("lgname", self.credentials.username.as_str()),
+            ("lgpassword", self.credentials.password.expose()),
+            ("lgtoken", login_token),
+            ("format", "json"),
+        ];
+        let login_request = self.request(api, api.client().post(url.clone()).form(&form))?;
+        let j = self.send(api, &url, login_request).await?;
+        match j["login"]["result"].as_str() {
+            Some("Success") => {
+                self.logged_in = true;
+                Ok(())
+            }
+            result => {
+                let reason = j["login"]["reason"]
+                    .as_str()
+                    .or(result)
+                    .unwrap_or("no login result");
+                self.rejection = Some(reason.to_string());
+                Err(RestApiError::LoginFailed(reason.to_string()))
+            }
+        }
+    }
+
+    /// The Action API next to the REST API, e.g. `https://example.org/w/api.php`.
+    fn action_api_url(api: &RestApi) -> Result<Url, RestApiError> {
+        let base = api
+            .api_url()
+            .strip_suffix("rest.php")
+            .unwrap_or(api.api_url());
+        Url::parse(&format!("{base}api.php"))
+            .map_err(|_| RestApiError::RestApiUrlInvalid(api.api_url().to_string()))
+    }
+
+    /// Builds a request with the user agent and the session cookies so far.
+    fn request(
+        &self,
+        api: &RestApi,
+        builder: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Request, RestApiError> {
+        let mut request = builder
+            .header(reqwest::header::USER_AGENT, api.user_agent())
+            .build()?;
+        if let Some(cookies) = self.cookie_header(request.url()) {
+            request
+                .headers_mut()
+                .insert(reqwest::header::COOKIE, cookies);
+        }
+        Ok(request)
+    }
+
+    /// Sends a request through the transport, keeps the cookies it sets, and returns the JSON
+    /// body.
+    async fn send(
+        &self,
+        api: &RestApi,
+        url: &Url,
+        request: reqwest::Request,
+    ) -> Result<Value, RestApiError> {
+        let response = api.transport().execute(request).await?;
+        self.jar
+            .set_cookies(&mut response.headers().get_all(SET_COOKIE).iter(), url);
+        if !response.status().is_success() {
+            return Err(RestApiError::from_response(response).await);
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityId, HttpPut};
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Mounts a stand-in `api.php` that accepts `ExampleUser@Bot` with password `secret`.
+    async fn mount_action_api(mock_server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/w/api.php"))
+            .and(query_param("meta", "tokens"))
+            .and(query_param("type", "login"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Set-Cookie", "wiki_session=anonymous; Path=/; HttpOnly")
+                    .set_body_json(json!({"query": {"tokens": {"logintoken": "abc+\\"}}})),
+            )
+            .mount(mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/w/api.php"))
+            .and(header("Cookie", "wiki_session=anonymous"))
+            .and(body_string_contains("action=login"))
+            .and(body_string_contains("lgname=ExampleUser%40Bot"))
+            .and(body_string_contains("lgpassword=secret"))
+            .and(body_string_contains("lgtoken=abc%2B%5C"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Set-Cookie", "wiki_session=logged_in; Path=/; HttpOnly")
+                    .set_body_json(
+                        json!({"login": {"result": "Success", "lgusername": "ExampleUser"}}),
+                    ),
+            )
+            .mount(mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/w/api.php"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "login": {"result": "Failed", "reason": "Incorrect username or password entered."}
+            })))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[test]
+    fn test_bot_password_debug_redacts_password() {
+        let credentials = BotPassword::new("ExampleUser@Bot", "secret");
+        let debug = format!("{credentials:?}");
+        assert!(debug.contains("ExampleUser@Bot"));
+        assert!(!debug.contains("secret"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_login() {
+        let mock_server = MockServer::start().await;
+        mount_action_api(&mock_server).await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut session = CookieSession::new(BotPassword::new("ExampleUser@Bot", "secret"));
+        session.login(&api).await.unwrap();
+        assert!(session.is_logged_in());
+        let url = Url::parse(&(mock_server.uri() + "/w/rest.php/wikibase/v1")).unwrap();
+        assert_eq!(
+            session.cookie_header(&url).unwrap(),
+            "wiki_session=logged_in"
+        );
+
+        let mut wrong = CookieSession::new(BotPassword::new("ExampleUser@Bot", "wrong"));
+        match wrong.login(&api).await {
+            Err(RestApiError::LoginFailed(reason)) => {
+                assert_eq!(reason, "Incorrect username or password entered.");
+            }
+            other => panic!("Wrong result: {other:?}"),
+        }
+        assert!(!wrong.is_logged_in());
+        assert_eq!(
+            wrong.rejection(),
+            Some("Incorrect username or password entered.")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_session_debug_redacts_cookies() {
+        let mock_server = MockServer::start().await;
+        mount_action_api(&mock_server).await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_bot_password("ExampleUser@Bot", "secret")
+            .build()
+            .unwrap();
+        api.login().await.unwrap();
+        let token = api.token();
+        let token = token.read().await;
+        let session = token.session().unwrap();
+        assert!(session.is_logged_in());
+        let debug = format!("{session:?}");
+        assert!(debug.contains("jar: [REDACTED]"));
+        for output in [debug, format!("{token:?}"), format!("{api:?}")] {
+            assert!(!output.contains("wiki_session"), "{output}");
+            assert!(!output.contains("=logged_in"), "{output}");
+            assert!(!output.contains("\"secret\""), "{output}");
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_rejected_login_is_not_repeated() {
+        let mock_server = MockServer::start().await;
+        mount_action_api(&mock_server).await;
+        Mock::given(method("PUT"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42/labels/en"))
+            .and(header("Cookie", "wiki_session=logged_in"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!("Douglas Adams")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_max_retries(0)
+            .with_bot_password("ExampleUser@Bot", "wrong")
+            .build()
+            .unwrap();
+        let label = crate::label::Label::new("en", "Douglas Adams");
+        for _ in 0..3 {
+            let error = label.put(&EntityId::item("Q42"), &api).await.unwrap_err();
+            assert!(matches!(error, RestApiError::LoginFailed(_)), "{error:?}");
+        }
+        let logins = || async {
+            let requests = mock_server.received_requests().await.unwrap();
+            requests
+                .iter()
+                .filter(|request| request.method == wiremock::http::Method::POST)
+                .count()
+        };
+        assert_eq!(logins().await, 1);
+
+        // An explicit login tries again.
+        api.token()
+            .write()
+            .await
+            .set_bot_password("ExampleUser@Bot", "secret");
+        api.login().await.unwrap();
+        label.put(&EntityId::item("Q42"), &api).await.unwrap();
+        assert_eq!(logins().await, 2);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_requests_authorized_by_cookie() {
+        let mock_server = MockServer::start().await;
+        mount_action_api(&mock_server).await;
+        Mock::given(method("PUT"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42/labels/en"))
+            .and(header("Cookie", "wiki_session=logged_in"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!("Douglas Adams")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_bot_password("ExampleUser@Bot", "secret")
+            .build()
+            .unwrap();
+        assert!(!api.token().read().await.session().unwrap().is_logged_in());
+
+        // The first request logs in, and is sent with the session cookie.
+        let label = crate::label::Label::new("en", "Douglas Adams");
+        label.put(&EntityId::item("Q42"), &api).await.unwrap();
+        assert!(api.token().read().await.session().unwrap().is_logged_in());
+
+        let unconfigured = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(matches!(
+            unconfigured.login().await,
+            Err(RestApiError::BotPasswordRequired)
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::RestApiError;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+const HTTP_METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// What a Wikibase REST API installation supports, as described by its `OpenAPI` document.
+///
+/// Get it with `RestApi::probe_capabilities`, or let `RestApiBuilder::build_and_probe` fetch
+/// it. A `RestApi` that knows its capabilities fails unsupported operations up front with
+/// `RestApiError::NotImplementedInRestApi`, without a round trip.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    version: Option<String>,
+    endpoints: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Capabilities {
+    /// Parses the `OpenAPI` document, as returned by `RestApi::get_openapi_json`.
+    /// # Errors
+    /// Returns an error if the document has no `paths` object.
+    pub fn from_openapi_json(j: &Value) -> Result<Self, RestApiError> {
+        let paths = j["paths"]
+            .as_object()
+            .ok_or_else(|| RestApiError::MissingOrInvalidField {
+                field: "paths".into(),
+                j: j.to_owned(),
+            })?;
+        let endpoints = paths
+            .iter()
+            .map(|(path, operations)| {
+                let methods = HTTP_METHODS
+                    .iter()
+                    .filter(|method| operations.get(**method).is_some())
+                    .map(|method| method.to_ascii_uppercase())
+                    .collect();
+                (path.to_owned(), methods)
+            })
+            .collect();
+        Ok(Self {
+            version: j["info"]["version"].as_str().map(str::to_string),
+            endpoints,
+        })
+    }
+
+    /// Returns the version of the API description (`info.version`), e.g. "1.1".
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the path templates, e.g. `/v1/entities/items/{item_id}`.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.keys().map(String::as_str)
+    }
+
+    /// Returns the methods supported for a path template, e.g. `["GET", "PATCH"]`.
+    pub fn methods(&self, path: &str) -> Vec<reqwest::Method> {
+        self.endpoints
+            .get(path)
+            .into_iter()
+            .flatten()
+            .filter_map(|method| method.parse().ok())
+            .collect()
+    }
+
+    /// Returns `true` if `method` is supported for `path`, which is either a template or a
+    /// concrete path like `/v1/entities/items/Q42/labels/en`. Template parameters match any
+    /// single path segment.
+    pub fn supports(&self, method: &reqwest::Method, path: &str) -> bool {
+        self.endpoints.iter().any(|(template, methods)| {
+            methods.contains(method.as_str()) && Self::path_matches(template, path)
+        })
+    }
+
+    /// Returns `true` if new items can be created (`POST /v1/entities/items`).
+    pub fn can_create_items(&self) -> bool {
+        self.supports(&reqwest::Method::POST, "/v1/entities/items")
+    }
+
+    /// Returns `true` if new properties can be created (`POST /v1/entities/properties`).
+    pub fn can_create_properties(&self) -> bool {
+        self.supports(&reqwest::Method::POST, "/v1/entities/properties")
+    }
+
+    /// Returns `true` if labels can be read with language fallback.
+    pub fn has_label_fallback(&self) -> bool {
+        self.supports(
+            &reqwest::Method::GET,
+            "/v1/entities/items/{item_id}/labels_with_language_fallback/{language_code}",
+        )
+    }
+
+    /// Returns `true` if the map of property data types can be read
+    /// (`GET /v1/property-data-types`).
+    pub fn has_property_data_types(&self) -> bool {
+        self.supports(&reqwest::Method::GET, "/v1/property-data-types")
+    }
+
+    pub(crate) fn path_matches(template: &str, path: &str) -> bool {
+        let mut template_segments = template.split('/');
+        let mut path_segments = path.split('/');
+        loop {
+            match (template_segments.next(), path_segments.next()) {
+                (None, None) => return true,
+                (Some(t), Some(p)) if t == p || (t.starts_with('{') && t.ends_with('}')) => {}
+                _ => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entity::Entity, Item, RestApi};
+    use reqwest::Method;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn openapi() -> Value {
+        serde_json::from_str(&std::fs::read_to_string("spec/openapi.json").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_from_openapi_json() {
+        let capabilities = Capabilities::from_openapi_json(&openapi()).unwrap();
+        assert_eq!(capabilities.version(), Some("1.1"));
+        assert!(capabilities
+            .paths()
+            .any(|p| p == "/v1/statements/{statement_id}"));
+        assert_eq!(
+            capabilities.methods("/v1/entities/items/{item_id}"),
+            vec![Method::GET, Method::PATCH]
+        );
+        assert!(capabilities.can_create_items());
+        assert!(capabilities.can_create_properties());
+        assert!(capabilities.has_label_fallback());
+        assert!(capabilities.has_property_data_types());
+        assert!(capabilities.supports(&Method::GET, "/v1/entities/items/Q42/labels/en"));
+        assert!(!capabilities.supports(&Method::DELETE, "/v1/entities/items/Q42"));
+        assert!(!capabilities.supports(&Method::DELETE, "/v1/entities/items/Q42/labels"));
+        assert!(!capabilities.supports(&Method::GET, "/v1/entities/items/Q42/labels/en/x"));
+    }
+
+    #[test]
+    fn test_from_openapi_json_without_paths() {
+        let result = Capabilities::from_openapi_json(&serde_json::json!({"openapi": "3.1.0"}));
+        assert!(matches!(
+            result,
+            Err(RestApiError::MissingOrInvalidField { .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_build_and_probe() {
+        let mut doc = openapi();
+        doc["paths"]
+            .as_object_mut()
+            .unwrap()
+            .remove("/v1/entities/items");
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/openapi.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&doc))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .build_and_probe()
+            .await
+            .unwrap();
+        assert!(!api.capabilities().unwrap().can_create_items());
+
+        let result = Item::default().post(&api).await;
+        let Err(RestApiError::NotImplementedInRestApi { method, path }) = result else {
+            panic!("expected NotImplementedInRestApi, got {result:?}");
+        };
+        assert_eq!(method, Method::POST);
+        assert_eq!(path, "/entities/items");
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_with_capabilities() {
+        let capabilities = Capabilities::from_openapi_json(&openapi()).unwrap();
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_capabilities(capabilities)
+            .build()
+            .unwrap();
+        // Search is only described for v0, so it fails without a request.
+        let search =
+            crate::search::Search::items("Douglas Adams", crate::Language::try_new("en").unwrap());
+        let result = search.get(&api).await;
+        assert!(matches!(
+            result,
+            Err(RestApiError::NotImplementedInRestApi { .. })
+        ));
+        let get = api
+            .wikibase_request_builder(
+                "/entities/items/Q42",
+                std::collections::HashMap::new(),
+                Method::GET,
+            )
+            .await;
+        assert!(get.is_ok());
+    }
+}
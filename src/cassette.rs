@@ -0,0 +1,530 @@
+use crate::{transport::Transport, RestApiError};
+use futures::future::BoxFuture;
+use reqwest::header::{
+    HeaderMap, AUTHORIZATION, CONTENT_TYPE, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Headers that carry credentials; they are never written to a cassette.
+const SCRUBBED_HEADERS: [reqwest::header::HeaderName; 4] =
+    [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+/// Form fields that carry credentials, in `OAuth2` token requests and the Action API login.
+const SCRUBBED_FORM_FIELDS: [&str; 6] = [
+    "client_secret",
+    "refresh_token",
+    "code",
+    "code_verifier",
+    "lgpassword",
+    "lgtoken",
+];
+/// JSON fields that carry credentials, in `OAuth2` token responses and Action API tokens.
+const SCRUBBED_JSON_FIELDS: [&str; 5] = [
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "logintoken",
+    "csrftoken",
+];
+/// What a scrubbed credential is replaced with.
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Sends requests to the server and writes every exchange to the cassette file.
+    Record,
+    /// Answers requests from the cassette file, without any network access.
+    Replay,
+}
+
+/// A JSON file of recorded request/response pairs, for offline tests against real payloads.
+///
+/// Set it with `RestApiBuilder::with_cassette`. In record mode, requests go through the
+/// configured transport and each exchange is appended to the file, with credential headers
+/// removed and credentials in bodies (e.g. of `OAuth2` token requests and the Action API
+/// login) replaced by `[REDACTED]`. In replay mode, a request is answered with the first
+/// unused recording of the same method, URL and body (or the last one, once all have been
+/// used); any other request fails with `RestApiError::Cassette`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+}
+
+impl Cassette {
+    /// Records to `path`, replacing any existing file.
+    pub fn record<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            mode: CassetteMode::Record,
+        }
+    }
+
+    /// Replays from `path`.
+    pub fn replay<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            mode: CassetteMode::Replay,
+        }
+    }
+
+    /// Returns the path of the cassette file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the mode.
+    pub const fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+}
+
+/// A request or response body. JSON is stored as-is, so cassettes read like the
+/// fixtures in `test_data/`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordedBody {
+    Json(Value),
+    Text(String),
+}
+
+impl RecordedBody {
+    /// Reads a body, with credentials scrubbed. `form` is whether it is form-encoded.
+    fn from_bytes(bytes: &[u8], form: bool) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        if let Ok(mut j) = serde_json::from_slice(bytes) {
+            scrub_json(&mut j);
+            return Some(Self::Json(j));
+        }
+        let text = String::from_utf8_lossy(bytes);
+        if form {
+            return Some(Self::Text(scrub_form(&text)));
+        }
+        Some(Self::Text(text.into_owned()))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Json(j) => j.to_string().into_bytes(),
+            Self::Text(text) => text.clone().into_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<RecordedBody>,
+}
+
+impl RecordedRequest {
+    fn from_request(request: &reqwest::Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: scrubbed_headers(request.headers()),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .and_then(|bytes| RecordedBody::from_bytes(bytes, is_form(request.headers()))),
+        }
+    }
+
+    /// Two requests match if method, URL and body are the same; headers are ignored.
+    fn matches(&self, other: &Self) -> bool {
+        self.method == other.method && self.url == other.url && self.body == other.body
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<RecordedBody>,
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Result<reqwest::Response, RestApiError> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let body = self
+            .body
+            .as_ref()
+            .map(RecordedBody::to_bytes)
+            .unwrap_or_default();
+        let response = builder
+            .body(body)
+            .map_err(|e| RestApiError::Cassette(e.to_string()))?;
+        Ok(reqwest::Response::from(response))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+/// Collects headers into a map, dropping credentials. Repeated headers are joined.
+fn scrubbed_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        if SCRUBBED_HEADERS.contains(name) {
+            continue;
+        }
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        map.entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+/// Returns `true` if the headers announce a form-encoded body.
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"))
+}
+
+/// Replaces the values of credential fields in a form-encoded body. Fields are sorted by
+/// name, as forms built from maps have no stable order.
+fn scrub_form(body: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(&format!("http://cassette.invalid/?{body}")) else {
+        return body.to_string();
+    };
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if SCRUBBED_FORM_FIELDS.contains(&name.as_ref()) {
+                REDACTED.into()
+            } else {
+                value
+            };
+            (name.into_owned(), value.into_owned())
+        })
+        .collect();
+    pairs.sort();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.query().unwrap_or_default().to_string()
+}
+
+/// Replaces the values of credential fields anywhere in a JSON body.
+fn scrub_json(j: &mut Value) {
+    match j {
+        Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                if value.is_string() && SCRUBBED_JSON_FIELDS.contains(&name.as_str()) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    scrub_json(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(scrub_json),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    used: Vec<bool>,
+}
+
+/// The transport behind a `Cassette`. Created by `RestApiBuilder::build`.
+#[derive(Debug)]
+pub(crate) struct CassetteTransport {
+    cassette: Cassette,
+    inner: Arc<dyn Transport>,
+    tape: Mutex<Tape>,
+}
+
+impl CassetteTransport {
+    /// Wraps `inner`. In replay mode, the cassette file is read right away.
+    pub(crate) fn new(cassette: Cassette, inner: Arc<dyn Transport>) -> Result<Self, RestApiError> {
+        let mut tape = Tape::default();
+        if cassette.mode == CassetteMode::Replay {
+            let text = std::fs::read_to_string(&cassette.path).map_err(|e| {
+                RestApiError::Cassette(format!("cannot read {}: {e}", cassette.path.display()))
+            })?;
+            tape.interactions = serde_json::from_str(&text)?;
+            tape.used = vec![false; tape.interactions.len()];
+        }
+        Ok(Self {
+            cassette,
+            inner,
+            tape: Mutex::new(tape),
+        })
+    }
+
+    fn replay(&self, request: &reqwest::Request) -> Result<reqwest::Response, RestApiError> {
+        let wanted = RecordedRequest::from_request(request);
+        let mut tape = self
+            .tape
+            .lock()
+            .map_err(|e| RestApiError::Cassette(e.to_string()))?;
+        let Tape { interactions, used } = &mut *tape;
+        let matching: Vec<usize> = interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| interaction.request.matches(&wanted))
+            .map(|(index, _)| index)
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|index| !used[*index])
+            .or_else(|| matching.last().copied())
+            .ok_or_else(|| {
+                RestApiError::Cassette(format!(
+                    "no recorded response in {} for {} {}",
+                    self.cassette.path.display(),
+                    wanted.method,
+                    wanted.url
+                ))
+            })?;
+        used[index] = true;
+        interactions[index].response.to_response()
+    }
+
+    async fn record(&self, request: reqwest::Request) -> Result<reqwest::Response, RestApiError> {
+        let recorded_request = RecordedRequest::from_request(&request);
+        let response = self.inner.execute(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response.bytes().await?;
+        let recorded_response = RecordedResponse {
+            status: status.as_u16(),
+            headers: scrubbed_headers(&headers),
+            body: RecordedBody::from_bytes(&bytes, is_form(&headers)),
+        };
+
+        // Hand the caller the original response, credentials and all.
+        let mut builder = http::Response::builder().status(status);
+        if let Some(response_headers) = builder.headers_mut() {
+            *response_headers = headers;
+        }
+        let original = builder
+            .body(bytes.to_vec())
+            .map_err(|e| RestApiError::Cassette(e.to_string()))?;
+
+        let mut tape = self
+            .tape
+            .lock()
+            .map_err(|e| RestApiError::Cassette(e.to_string()))?;
+        tape.interactions.push(Interaction {
+            request: recorded_request,
+            response: recorded_response,
+        });
+        // Written after every exchange, so the cassette is complete even if the test panics.
+        let text = serde_json::to_string_pretty(&tape.interactions)?;
+        std::fs::write(&self.cassette.path, text).map_err(|e| {
+            RestApiError::Cassette(format!(
+                "cannot write {}: {e}",
+                self.cassette.path.display()
+            ))
+        })?;
+        Ok(reqwest::Response::from(original))
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, Result<reqwest::Response, RestApiError>> {
+        match self.cassette.mode {
+            CassetteMode::Record => Box::pin(self.record(request)),
+            CassetteMode::Replay => {
+                let result = self.replay(&request);
+                Box::pin(async move { result })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entity::Entity, EntityId, Item, RestApi};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn temp_cassette() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "wikibase_rest_api_cassette_{}.json",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_record_then_replay() {
+        let v: Value =
+            serde_json::from_str(&std::fs::read_to_string("test_data/Q42.json").unwrap()).unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"1234\"")
+                    .set_body_json(&v),
+            )
+            .mount(&mock_server)
+            .await;
+        let api_url = mock_server.uri() + "/w/rest.php";
+        let file = temp_cassette();
+
+        let record_api = RestApi::builder(&api_url)
+            .unwrap()
+            .with_access_token("secret-token")
+            .with_cassette(Cassette::record(&file))
+            .build()
+            .unwrap();
+        let recorded = Item::get(EntityId::item("Q42"), &record_api).await.unwrap();
+        let text = std::fs::read_to_string(&file).unwrap();
+        assert!(!text.contains("secret-token"));
+        assert!(text.contains("\"Douglas Adams\""));
+
+        // Replay works without the server.
+        drop(mock_server);
+        let replay_api = RestApi::builder(&api_url)
+            .unwrap()
+            .with_cassette(Cassette::replay(&file))
+            .build()
+            .unwrap();
+        let replayed = Item::get(EntityId::item("Q42"), &replay_api).await.unwrap();
+        assert_eq!(replayed.id(), recorded.id());
+        assert_eq!(replayed.header_info().revision_id(), Some(1234));
+        // A recognized request can be replayed more than once.
+        assert!(Item::get(EntityId::item("Q42"), &replay_api).await.is_ok());
+
+        let unknown = Item::get(EntityId::item("Q1"), &replay_api).await;
+        assert!(matches!(unknown, Err(RestApiError::Cassette(_))));
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_replay_missing_file() {
+        let result = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_cassette(Cassette::replay(temp_cassette()))
+            .build();
+        assert!(matches!(result, Err(RestApiError::Cassette(_))));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_token_refresh_is_scrubbed() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "new-access-secret",
+                "refresh_token": "new-refresh-secret",
+                "expires_in": 14400,
+            })))
+            .mount(&mock_server)
+            .await;
+        let api_url = mock_server.uri() + "/w/rest.php";
+        let file = temp_cassette();
+        let api_with = |cassette| {
+            let api = RestApi::builder(&api_url)
+                .unwrap()
+                .with_oauth2_info("client", "client-secret")
+                .with_cassette(cassette)
+                .build()
+                .unwrap();
+            api.token
+                .try_write()
+                .unwrap()
+                .set_tokens(None, Some("old-refresh-secret".into()));
+            api
+        };
+
+        let record_api = api_with(Cassette::record(&file));
+        let recorded_token = record_api.token();
+        recorded_token
+            .write()
+            .await
+            .renew_access_token(&record_api)
+            .await
+            .unwrap();
+        assert_eq!(
+            recorded_token.read().await.get(),
+            &Some("new-access-secret".into())
+        );
+        let text = std::fs::read_to_string(&file).unwrap();
+        for secret in [
+            "client-secret",
+            "old-refresh-secret",
+            "new-access-secret",
+            "new-refresh-secret",
+        ] {
+            assert!(!text.contains(secret), "{secret} in {text}");
+        }
+        assert!(text.contains("grant_type=refresh_token"));
+
+        // The scrubbed exchange still replays.
+        drop(mock_server);
+        let replay_api = api_with(Cassette::replay(&file));
+        let replayed_token = replay_api.token();
+        replayed_token
+            .write()
+            .await
+            .renew_access_token(&replay_api)
+            .await
+            .unwrap();
+        assert_eq!(replayed_token.read().await.get(), &Some(REDACTED.into()));
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_scrub_bodies() {
+        assert_eq!(
+            scrub_form("action=login&lgname=User%40Bot&lgpassword=p%26ss&lgtoken=abc%2B%5C"),
+            "action=login&lgname=User%40Bot&lgpassword=%5BREDACTED%5D&lgtoken=%5BREDACTED%5D"
+        );
+        let mut j = serde_json::json!({
+            "query": {"tokens": {"logintoken": "abc+\\"}},
+            "code": "item-not-found",
+        });
+        scrub_json(&mut j);
+        assert_eq!(j["query"]["tokens"]["logintoken"], REDACTED);
+        // Error payloads keep their `code`.
+        assert_eq!(j["code"], "item-not-found");
+    }
+
+    #[test]
+    fn test_scrubbed_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer x".parse().unwrap());
+        headers.append("X-Thing", "a".parse().unwrap());
+        headers.append("X-Thing", "b".parse().unwrap());
+        let map = scrubbed_headers(&headers);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["x-thing"], "a, b");
+    }
+}
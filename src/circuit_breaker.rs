@@ -0,0 +1,323 @@
+use crate::RestApiError;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOL_OFF: Duration = Duration::from_secs(30);
+
+/// Settings for the circuit breaker.
+///
+/// After `failure_threshold` consecutive failed requests (5xx responses, connection errors or
+/// timeouts), the circuit opens and every request fails right away with
+/// `RestApiError::CircuitOpen`, without reaching the server. Once `cool_off` has passed, a
+/// single probe request is let through: if it succeeds the circuit closes again, otherwise it
+/// stays open for another `cool_off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_off: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cool_off: DEFAULT_COOL_OFF,
+        }
+    }
+}
+
+impl CircuitBreaker {
+    /// Creates the default settings: open after 5 consecutive failures, probe after 30 seconds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of consecutive failures that opens the circuit. Default is 5. Zero is
+    /// treated as 1.
+    pub const fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = if failure_threshold == 0 {
+            1
+        } else {
+            failure_threshold
+        };
+        self
+    }
+
+    /// Sets how long the circuit stays open before a probe is let through. Default is 30
+    /// seconds.
+    pub const fn with_cool_off(mut self, cool_off: Duration) -> Self {
+        self.cool_off = cool_off;
+        self
+    }
+
+    /// Returns the number of consecutive failures that opens the circuit.
+    pub const fn failure_threshold(&self) -> u32 {
+        self.failure_threshold
+    }
+
+    /// Returns how long the circuit stays open before a probe is let through.
+    pub const fn cool_off(&self) -> Duration {
+        self.cool_off
+    }
+}
+
+/// The state of a circuit breaker, see `RestApi::circuit_state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    Closed,
+    /// Requests fail fast, until the cool-off has passed.
+    Open,
+    /// The cool-off has passed; the next request is a probe.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// The shared breaker. Every request is admitted by `check` right before it is sent and
+/// reports its outcome through the returned `Admission`.
+#[derive(Debug)]
+pub(crate) struct CircuitController {
+    settings: CircuitBreaker,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitController {
+    pub(crate) const fn new(settings: CircuitBreaker) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// Returns the current state.
+    pub(crate) fn state(&self) -> CircuitState {
+        let Ok(state) = self.state.lock() else {
+            return CircuitState::Closed;
+        };
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.settings.cool_off => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Fails with `RestApiError::CircuitOpen` while the circuit is open, without admitting a
+    /// request, so callers need not wait for a rate limit only to be rejected by `check`.
+    pub(crate) fn fail_fast(&self) -> Result<(), RestApiError> {
+        let Ok(state) = self.state.lock() else {
+            return Ok(());
+        };
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.settings.cool_off => Err(
+                RestApiError::CircuitOpen(self.settings.cool_off - opened_at.elapsed()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Admits a request, or fails with `RestApiError::CircuitOpen` carrying the time left until
+    /// the next probe. Once the cool-off has passed, only one probe is admitted at a time.
+    pub(crate) fn check(&self) -> Result<Admission<'_>, RestApiError> {
+        let admitted = |probe| {
+            Ok(Admission {
+                controller: self,
+                probe,
+            })
+        };
+        // A poisoned lock means a panic elsewhere; don't block callers forever.
+        let Ok(mut state) = self.state.lock() else {
+            return admitted(false);
+        };
+        let Some(opened_at) = state.opened_at else {
+            return admitted(false);
+        };
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.settings.cool_off {
+            return Err(RestApiError::CircuitOpen(self.settings.cool_off - elapsed));
+        }
+        if state.probe_in_flight {
+            return Err(RestApiError::CircuitOpen(Duration::ZERO));
+        }
+        state.probe_in_flight = true;
+        drop(state);
+        admitted(true)
+    }
+
+    /// Records the outcome of an admitted request. A failure of the probe, or the
+    /// `failure_threshold`-th failure in a row, (re)opens the circuit; a success closes it.
+    /// While the circuit is open, only the probe's outcome counts: requests admitted before it
+    /// opened may still finish.
+    fn record(&self, probe: bool, failed: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if probe {
+            state.probe_in_flight = false;
+        } else if state.opened_at.is_some() {
+            return;
+        }
+        if !failed {
+            state.consecutive_failures = 0;
+            state.opened_at = None;
+            return;
+        }
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if probe || state.consecutive_failures >= self.settings.failure_threshold {
+            state.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Whether the outcome of a request counts as a failure of the server.
+    pub(crate) fn is_failure(result: &Result<reqwest::Response, RestApiError>) -> bool {
+        match result {
+            Ok(response) => response.status().is_server_error(),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        }
+    }
+}
+
+/// A request admitted by `CircuitController::check`. Its outcome is reported with `record`;
+/// if it is dropped unreported (e.g. the request was cancelled), the probe slot it may hold
+/// is released, so the next request can probe instead.
+#[derive(Debug)]
+pub(crate) struct Admission<'a> {
+    controller: &'a CircuitController,
+    probe: bool,
+}
+
+impl Admission<'_> {
+    /// Records the outcome of the admitted request.
+    pub(crate) fn record(mut self, failed: bool) {
+        self.controller
+            .record(std::mem::take(&mut self.probe), failed);
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            if let Ok(mut state) = self.controller.state.lock() {
+                state.probe_in_flight = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RestApi;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_settings() {
+        let settings = CircuitBreaker::new()
+            .with_failure_threshold(0)
+            .with_cool_off(Duration::from_secs(1));
+        assert_eq!(settings.failure_threshold(), 1);
+        assert_eq!(settings.cool_off(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitController::new(CircuitBreaker::new().with_failure_threshold(2));
+        breaker.check().unwrap().record(true);
+        breaker.check().unwrap().record(false);
+        breaker.check().unwrap().record(true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.check().unwrap().record(true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        let result = breaker.check();
+        assert!(result.is_err_and(|e| e.is_circuit_open()));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // sleeps on the realtime clock
+    fn test_half_open_probe() {
+        let breaker = CircuitController::new(
+            CircuitBreaker::new()
+                .with_failure_threshold(1)
+                .with_cool_off(Duration::from_millis(20)),
+        );
+        breaker.check().unwrap().record(true);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // One probe at a time.
+        let probe = breaker.check().unwrap();
+        assert!(breaker.check().is_err());
+        // A failed probe opens the circuit again.
+        probe.record(true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.fail_fast().is_err());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.fail_fast().is_ok());
+        breaker.check().unwrap().record(false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // sleeps on the realtime clock
+    fn test_probe_outcome_belongs_to_probe() {
+        let breaker = CircuitController::new(
+            CircuitBreaker::new()
+                .with_failure_threshold(2)
+                .with_cool_off(Duration::from_millis(20)),
+        );
+        // Admitted before the circuit opened, and still in flight.
+        let straggler = breaker.check().unwrap();
+        breaker.check().unwrap().record(true);
+        breaker.check().unwrap().record(true);
+        std::thread::sleep(Duration::from_millis(30));
+
+        // A dropped probe (e.g. a cancelled request) frees the slot for the next one.
+        drop(breaker.check().unwrap());
+        let probe = breaker.check().unwrap();
+        // The straggler failing does not count as the probe failing...
+        straggler.record(true);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.check().is_err());
+        // ...only the probe's own outcome does.
+        probe.record(false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_fails_fast_when_open() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_retry_base_delay(Duration::from_millis(1))
+            .with_circuit_breaker(CircuitBreaker::new().with_failure_threshold(3))
+            .build()
+            .unwrap();
+        assert_eq!(api.circuit_state(), Some(CircuitState::Closed));
+
+        // The third attempt opens the circuit, so the last retry never reaches the server.
+        let first = api.get_openapi_json().await;
+        assert!(first.is_err_and(|e| e.is_circuit_open()));
+        assert_eq!(api.circuit_state(), Some(CircuitState::Open));
+        let second = api.get_openapi_json().await;
+        assert!(second.is_err_and(|e| e.is_circuit_open()));
+        mock_server.verify().await;
+    }
+}
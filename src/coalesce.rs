@@ -0,0 +1,252 @@
+use crate::RestApiError;
+use futures::{
+    channel::oneshot,
+    future::{BoxFuture, FutureExt, Shared},
+};
+use reqwest::header::{
+    HeaderMap, AUTHORIZATION, COOKIE, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_UNMODIFIED_SINCE,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+/// Headers that can change the answer to a GET, and so are part of the coalescing key.
+const KEY_HEADERS: [reqwest::header::HeaderName; 6] = [
+    IF_MATCH,
+    IF_NONE_MATCH,
+    IF_MODIFIED_SINCE,
+    IF_UNMODIFIED_SINCE,
+    AUTHORIZATION,
+    COOKIE,
+];
+
+/// A response with its body read, so it can be handed out more than once.
+#[derive(Debug, Clone)]
+pub(crate) struct BufferedResponse {
+    status: reqwest::StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl BufferedResponse {
+    pub(crate) async fn read(response: reqwest::Response) -> Result<Self, RestApiError> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        Ok(Self {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    pub(crate) fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub(crate) fn to_response(&self) -> reqwest::Response {
+        let mut response = http::Response::new(self.body.clone());
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        reqwest::Response::from(response)
+    }
+}
+
+/// What a follower gets from the leader of a coalesced request. The error is the leader's,
+/// duplicated, as the leader keeps the original.
+type Outcome = Result<Arc<BufferedResponse>, Arc<RestApiError>>;
+
+/// A request in flight, as seen by followers. Resolves to `None` if the leader was cancelled.
+type Flight = Shared<BoxFuture<'static, Option<Outcome>>>;
+
+/// Single-flight deduplication of GET requests. Concurrent requests with the same key share
+/// one call, made by the first of them (the leader); each caller gets its own copy of the
+/// response.
+#[derive(Debug, Default)]
+pub(crate) struct Coalescer {
+    in_flight: Mutex<HashMap<String, Flight>>,
+}
+
+impl Coalescer {
+    /// Returns the key for `request`, or `None` if it must not be shared.
+    pub(crate) fn key(request: &reqwest::Request) -> Option<String> {
+        if request.method() != reqwest::Method::GET {
+            return None;
+        }
+        let mut key = request.url().to_string();
+        for name in &KEY_HEADERS {
+            for value in request.headers().get_all(name) {
+                key.push('\n');
+                key.push_str(name.as_str());
+                key.push(':');
+                key.push_str(value.to_str().ok()?);
+            }
+        }
+        Some(key)
+    }
+
+    /// Runs `call`, unless a call with the same key is already in flight, in which case its
+    /// result is shared instead. The leader gets its own error as it is; followers get a
+    /// duplicate, see `RestApiError::duplicate`.
+    pub(crate) async fn execute<F>(
+        &self,
+        key: String,
+        call: F,
+    ) -> Result<reqwest::Response, RestApiError>
+    where
+        F: Future<Output = Result<reqwest::Response, RestApiError>>,
+    {
+        let sender = match self.in_flight.lock() {
+            Ok(mut in_flight) => match in_flight.entry(key.clone()) {
+                Entry::Occupied(flight) => Err(flight.get().clone()),
+                Entry::Vacant(slot) => {
+                    let (sender, receiver) = oneshot::channel();
+                    slot.insert(receiver.map(Result::ok).boxed().shared());
+                    Ok(sender)
+                }
+            },
+            // A poisoned lock means a panic elsewhere; just send the request.
+            Err(_) => return call.await,
+        };
+        let sender = match sender {
+            Ok(sender) => sender,
+            Err(flight) => {
+                return match flight.await {
+                    Some(Ok(response)) => Ok(response.to_response()),
+                    Some(Err(e)) => Err(e.duplicate()),
+                    // The leader was cancelled, so send the request after all.
+                    None => call.await,
+                };
+            }
+        };
+        let leader = Leader {
+            coalescer: self,
+            key,
+        };
+        let result = match call.await {
+            Ok(response) => BufferedResponse::read(response).await,
+            Err(e) => Err(e),
+        };
+        // Later callers start a new call rather than join one that has finished.
+        drop(leader);
+        match result {
+            Ok(response) => {
+                let response = Arc::new(response);
+                let _ = sender.send(Ok(response.clone()));
+                Ok(response.to_response())
+            }
+            Err(e) => {
+                let _ = sender.send(Err(Arc::new(e.duplicate())));
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Removes the leader's flight from the map when the leader finishes or is cancelled.
+struct Leader<'a> {
+    coalescer: &'a Coalescer,
+    key: String,
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.coalescer.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entity::Entity, EntityId, Item, RestApi};
+    use serde_json::Value;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_key() {
+        let client = reqwest::Client::new();
+        let url = "https://test.wikidata.org/w/rest.php/wikibase/v1/entities/items/Q42";
+        let plain = client.get(url).build().unwrap();
+        let conditional = client
+            .get(url)
+            .header(IF_NONE_MATCH, "\"1234\"")
+            .build()
+            .unwrap();
+        assert_ne!(Coalescer::key(&plain), Coalescer::key(&conditional));
+        assert_eq!(
+            Coalescer::key(&plain),
+            Coalescer::key(&client.get(url).build().unwrap())
+        );
+        assert!(Coalescer::key(&client.post(url).build().unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_concurrent_gets_share_one_call() {
+        let v: Value =
+            serde_json::from_str(&std::fs::read_to_string("test_data/Q42.json").unwrap()).unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(&v)
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_request_coalescing(true)
+            .build()
+            .unwrap();
+
+        let gets = (0..5).map(|_| Item::get(EntityId::item("Q42"), &api));
+        let items = futures::future::join_all(gets).await;
+        for item in items {
+            assert_eq!(item.unwrap().id(), &EntityId::item("Q42"));
+        }
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_leader_keeps_its_error() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_max_retries(0)
+            .with_timeout(Duration::from_millis(100))
+            .with_request_coalescing(true)
+            .build()
+            .unwrap();
+
+        // `join_all` polls in order, so the first request leads.
+        let gets = (0..3).map(|_| Item::get(EntityId::item("Q42"), &api));
+        let mut errors = futures::future::join_all(gets)
+            .await
+            .into_iter()
+            .map(Result::unwrap_err);
+        let leader = errors.next().unwrap();
+        assert!(matches!(&leader, RestApiError::Reqwest(e) if e.is_timeout()));
+        for follower in errors {
+            assert!(matches!(follower, RestApiError::SharedRequestFailed { .. }));
+            assert!(follower.is_timeout());
+            assert!(!follower.is_connect());
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+// Tokio's clock, so tests can pause and advance it.
+use tokio::{sync::Notify, time::Instant};
+
+const DEFAULT_MIN_CONCURRENCY: usize = 1;
+const DEFAULT_MAX_CONCURRENCY: usize = 32;
+const DEFAULT_INITIAL_CONCURRENCY: usize = 4;
+/// Factor applied to the limit when the server pushes back (429 or 5xx).
+const DECREASE_FACTOR: f64 = 0.5;
+/// Factor applied to the limit when latency exceeds the configured target.
+const LATENCY_DECREASE_FACTOR: f64 = 0.9;
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_EWMA_WEIGHT: f64 = 0.2;
+/// Minimum time between two decreases, so a burst of failures from requests that were
+/// already in flight only counts once.
+const MIN_DECREASE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Settings for the adaptive (AIMD) concurrency controller.
+///
+/// The number of in-flight requests grows by one per round of successful requests, and is
+/// halved when the server answers 429 or 5xx. A `Retry-After` header pauses new requests
+/// for the requested time. Optionally, the limit also backs off while the average latency
+/// is above a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+    initial: usize,
+    latency_target: Option<Duration>,
+}
+
+impl Default for AdaptiveConcurrency {
+    fn default() -> Self {
+        Self {
+            min: DEFAULT_MIN_CONCURRENCY,
+            max: DEFAULT_MAX_CONCURRENCY,
+            initial: DEFAULT_INITIAL_CONCURRENCY,
+            latency_target: None,
+        }
+    }
+}
+
+impl AdaptiveConcurrency {
+    /// Creates the default settings: start at 4 in-flight requests, between 1 and 32.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the lower bound for in-flight requests. Default is 1. Zero is treated as 1.
+    pub const fn with_min(mut self, min: usize) -> Self {
+        self.min = if min == 0 { 1 } else { min };
+        self
+    }
+
+    /// Sets the upper bound for in-flight requests. Default is 32.
+    pub const fn with_max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the starting number of in-flight requests. Default is 4.
+    pub const fn with_initial(mut self, initial: usize) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Backs off while the average latency is above `target`. Default is no target.
+    pub const fn with_latency_target(mut self, target: Duration) -> Self {
+        self.latency_target = Some(target);
+        self
+    }
+
+    /// Returns the lower bound.
+    pub const fn min(&self) -> usize {
+        self.min
+    }
+
+    /// Returns the upper bound (never below the lower bound).
+    pub fn max(&self) -> usize {
+        self.max.max(self.min)
+    }
+
+    /// Returns the starting limit, clamped to the bounds.
+    pub fn initial(&self) -> usize {
+        self.initial.clamp(self.min, self.max())
+    }
+
+    /// Returns the latency target, if any.
+    pub const fn latency_target(&self) -> Option<Duration> {
+        self.latency_target
+    }
+}
+
+#[derive(Debug)]
+struct ControllerState {
+    limit: f64,
+    in_flight: usize,
+    latency_ewma: Option<Duration>,
+    hold_until: Option<Instant>,
+    last_decrease: Option<Instant>,
+}
+
+/// The shared controller. Every request takes a `ConcurrencyPermit` before it is sent and
+/// reports its outcome through it.
+#[derive(Debug)]
+pub(crate) struct ConcurrencyController {
+    settings: AdaptiveConcurrency,
+    state: Mutex<ControllerState>,
+    notify: Notify,
+}
+
+impl ConcurrencyController {
+    pub(crate) fn new(settings: AdaptiveConcurrency) -> Self {
+        Self {
+            state: Mutex::new(ControllerState {
+                limit: settings.initial() as f64,
+                in_flight: 0,
+                latency_ewma: None,
+                hold_until: None,
+                last_decrease: None,
+            }),
+            settings,
+            notify: Notify::new(),
+        }
+    }
+
+    /// Returns the current number of allowed in-flight requests.
+    pub(crate) fn limit(&self) -> usize {
+        self.state
+            .lock()
+            .map_or(self.settings.min(), |state| Self::whole(state.limit))
+    }
+
+    fn whole(limit: f64) -> usize {
+        // The limit is always within [min, max], so the conversion cannot overflow.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let limit = limit.floor() as usize;
+        limit.max(1)
+    }
+
+    /// Waits for a free slot.
+    pub(crate) async fn acquire(self: &Arc<Self>) -> ConcurrencyPermit {
+        loop {
+            // Register for a wake-up before checking, so a release in between is not missed.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let hold = match self.state.lock() {
+                Ok(mut state) => {
+                    let now = Instant::now();
+                    match state.hold_until.filter(|until| *until > now) {
+                        Some(until) => Some(until - now),
+                        None if state.in_flight < Self::whole(state.limit) => {
+                            state.in_flight += 1;
+                            return ConcurrencyPermit {
+                                controller: self.clone(),
+                                started: now,
+                            };
+                        }
+                        None => None,
+                    }
+                }
+                // A poisoned lock means a panic elsewhere; don't block callers forever.
+                Err(_) => {
+                    return ConcurrencyPermit {
+                        controller: self.clone(),
+                        started: Instant::now(),
+                    }
+                }
+            };
+            match hold {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Adjusts the limit after a response.
+    fn record(
+        &self,
+        status: Option<reqwest::StatusCode>,
+        latency: Duration,
+        retry_after: Option<Duration>,
+    ) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        let ewma = state.latency_ewma.map_or(latency, |ewma| {
+            ewma.mul_f64(1.0 - LATENCY_EWMA_WEIGHT) + latency.mul_f64(LATENCY_EWMA_WEIGHT)
+        });
+        state.latency_ewma = Some(ewma);
+        if let Some(retry_after) = retry_after {
+            let until = now + retry_after;
+            state.hold_until = Some(state.hold_until.map_or(until, |hold| hold.max(until)));
+        }
+        let pushed_back = status.is_none_or(|status| {
+            status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        });
+        let too_slow = self
+            .settings
+            .latency_target()
+            .is_some_and(|target| ewma > target);
+        if pushed_back || too_slow {
+            let can_decrease = state
+                .last_decrease
+                .is_none_or(|last| now.duration_since(last) >= MIN_DECREASE_INTERVAL);
+            if can_decrease {
+                let factor = if pushed_back {
+                    DECREASE_FACTOR
+                } else {
+                    LATENCY_DECREASE_FACTOR
+                };
+                state.limit = (state.limit * factor).max(self.settings.min() as f64);
+                state.last_decrease = Some(now);
+            }
+        } else {
+            // Additive increase: about one more slot per round of successful requests.
+            state.limit = (state.limit + 1.0 / state.limit).min(self.settings.max() as f64);
+        }
+    }
+
+    fn release(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.notify.notify_waiters();
+    }
+}
+
+/// A slot for one in-flight request. The slot is released when the permit is dropped.
+#[derive(Debug)]
+pub(crate) struct ConcurrencyPermit {
+    controller: Arc<ConcurrencyController>,
+    started: Instant,
+}
+
+impl ConcurrencyPermit {
+    /// Reports the outcome of the request: its status (`None` if no response arrived) and
+    /// any `Retry-After` delay the server asked for.
+    pub(crate) fn finish(self, status: Option<reqwest::StatusCode>, retry_after: Option<Duration>) {
+        self.controller
+            .record(status, self.started.elapsed(), retry_after);
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.controller.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RestApi;
+    use reqwest::StatusCode;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn controller(settings: AdaptiveConcurrency) -> Arc<ConcurrencyController> {
+        Arc::new(ConcurrencyController::new(settings))
+    }
+
+    #[test]
+    fn test_settings_clamping() {
+        let settings = AdaptiveConcurrency::new()
+            .with_min(0)
+            .with_max(2)
+            .with_initial(10);
+        assert_eq!(settings.min(), 1);
+        assert_eq!(settings.max(), 2);
+        assert_eq!(settings.initial(), 2);
+        assert!(settings.latency_target().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_additive_increase_multiplicative_decrease() {
+        let c = controller(AdaptiveConcurrency::new().with_initial(4).with_max(6));
+        // About one round of successes raises the limit by one.
+        for _ in 0..5 {
+            c.acquire().await.finish(Some(StatusCode::OK), None);
+        }
+        assert_eq!(c.limit(), 5);
+        c.acquire()
+            .await
+            .finish(Some(StatusCode::TOO_MANY_REQUESTS), None);
+        assert_eq!(c.limit(), 2);
+        // A second failure right away (from a request already in flight) is not counted.
+        c.acquire()
+            .await
+            .finish(Some(StatusCode::SERVICE_UNAVAILABLE), None);
+        assert_eq!(c.limit(), 2);
+        tokio::time::advance(MIN_DECREASE_INTERVAL).await;
+        c.acquire()
+            .await
+            .finish(Some(StatusCode::SERVICE_UNAVAILABLE), None);
+        assert_eq!(c.limit(), 1);
+        // Throughput recovers, up to the maximum.
+        for _ in 0..100 {
+            c.acquire().await.finish(Some(StatusCode::OK), None);
+        }
+        assert_eq!(c.limit(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_connection_failure_decreases() {
+        let c = controller(AdaptiveConcurrency::new().with_initial(8));
+        c.acquire().await.finish(None, None);
+        assert_eq!(c.limit(), 4);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_latency_target() {
+        let c = controller(
+            AdaptiveConcurrency::new()
+                .with_initial(10)
+                .with_latency_target(Duration::from_nanos(1)),
+        );
+        let permit = c.acquire().await;
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        permit.finish(Some(StatusCode::OK), None);
+        assert_eq!(c.limit(), 9);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_limit_bounds_in_flight() {
+        let c = controller(AdaptiveConcurrency::new().with_initial(1));
+        let first = c.acquire().await;
+        let second = tokio::time::timeout(Duration::from_millis(20), c.acquire()).await;
+        assert!(second.is_err());
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_millis(20), c.acquire()).await;
+        assert!(third.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_retry_after_holds_new_requests() {
+        let c = controller(AdaptiveConcurrency::new());
+        c.acquire()
+            .await
+            .finish(Some(StatusCode::OK), Some(Duration::from_millis(50)));
+        let start = Instant::now();
+        drop(c.acquire().await);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_rest_api_reports_to_controller() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_max_retries(0)
+            .with_adaptive_concurrency(AdaptiveConcurrency::new().with_initial(8))
+            .build()
+            .unwrap();
+        assert_eq!(api.concurrency_limit(), Some(8));
+        assert!(api.get_openapi_json().await.is_err());
+        // Shared by clones.
+        assert_eq!(api.clone().concurrency_limit(), Some(4));
+    }
+}
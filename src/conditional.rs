@@ -0,0 +1,189 @@
+use crate::{RestApiError, RevisionMatch};
+
+/// The result of a conditional read, such as `Entity::get_if_changed`.
+///
+/// `NotModified` means the server confirmed that the known revision is still current
+/// (HTTP 304); it is not an error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conditional<T> {
+    Modified(T),
+    NotModified,
+}
+
+impl<T> Conditional<T> {
+    /// Returns `true` if a newer version was returned.
+    pub const fn is_modified(&self) -> bool {
+        matches!(self, Self::Modified(_))
+    }
+
+    /// Returns `true` if the known revision is still current.
+    pub const fn is_not_modified(&self) -> bool {
+        matches!(self, Self::NotModified)
+    }
+
+    /// Returns the newer version, if any.
+    pub fn modified(self) -> Option<T> {
+        match self {
+            Self::Modified(value) => Some(value),
+            Self::NotModified => None,
+        }
+    }
+
+    /// Returns a `RevisionMatch` that asks the server to answer 304 if `known_revision`
+    /// is still current.
+    pub(crate) fn revision_match(known_revision: u64) -> RevisionMatch {
+        let mut rm = RevisionMatch::default();
+        rm.set_modified_since_revisions(vec![known_revision]);
+        rm
+    }
+
+    /// Converts the result of a conditional request, turning a 304 error into `NotModified`.
+    pub(crate) fn from_result(result: Result<T, RestApiError>) -> Result<Self, RestApiError> {
+        match result {
+            Ok(value) => Ok(Self::Modified(value)),
+            Err(e) if e.is_not_modified() => Ok(Self::NotModified),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<T> From<Conditional<T>> for Option<T> {
+    fn from(conditional: Conditional<T>) -> Self {
+        conditional.modified()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::Entity,
+        get_put_delete::{HttpGet, HttpGetEntity},
+        label::Label,
+        EntityId, Item, RestApi, Sitelinks,
+    };
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock_conditional(mock_server: &MockServer, item_path: &str, body: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .and(header("If-None-Match", "\"100\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"101\"")
+                    .set_body_json(body),
+            )
+            .mount(mock_server)
+            .await;
+    }
+
+    async fn api(mock_server: &MockServer) -> RestApi {
+        RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_conditional_accessors() {
+        let modified = Conditional::Modified(1);
+        assert!(modified.is_modified());
+        assert!(!modified.is_not_modified());
+        assert_eq!(modified.modified(), Some(1));
+        let not_modified: Conditional<u8> = Conditional::NotModified;
+        assert!(not_modified.is_not_modified());
+        assert_eq!(Option::<u8>::from(not_modified), None);
+    }
+
+    #[test]
+    fn test_from_result_keeps_other_errors() {
+        let result: Result<u8, RestApiError> = Err(RestApiError::MissingId);
+        assert!(matches!(
+            Conditional::from_result(result),
+            Err(RestApiError::MissingId)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_entity_get_if_changed() {
+        let mock_server = MockServer::start().await;
+        mock_conditional(
+            &mock_server,
+            "/w/rest.php/wikibase/v1/entities/items/Q42",
+            json!({"id": "Q42"}),
+        )
+        .await;
+        let api = api(&mock_server).await;
+
+        let unchanged = Item::get_if_changed(EntityId::item("Q42"), 100, &api)
+            .await
+            .unwrap();
+        assert!(unchanged.is_not_modified());
+
+        let changed = Item::get_if_changed(EntityId::item("Q42"), 99, &api)
+            .await
+            .unwrap();
+        let item = changed.modified().unwrap();
+        assert_eq!(item.header_info().revision_id(), Some(101));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_http_get_if_changed() {
+        let mock_server = MockServer::start().await;
+        mock_conditional(
+            &mock_server,
+            "/w/rest.php/wikibase/v1/entities/items/Q42/labels/en",
+            json!("Douglas Adams"),
+        )
+        .await;
+        let api = api(&mock_server).await;
+        let id = EntityId::item("Q42");
+
+        let unchanged = Label::get_if_changed(&id, "en", 100, &api).await.unwrap();
+        assert!(unchanged.is_not_modified());
+        let changed = Label::get_if_changed(&id, "en", 99, &api).await.unwrap();
+        assert_eq!(changed.modified().unwrap().value(), "Douglas Adams");
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_http_get_entity_if_changed() {
+        let mock_server = MockServer::start().await;
+        mock_conditional(
+            &mock_server,
+            "/w/rest.php/wikibase/v1/entities/items/Q42/sitelinks",
+            json!({}),
+        )
+        .await;
+        let api = api(&mock_server).await;
+        let id = EntityId::item("Q42");
+
+        let unchanged = Sitelinks::get_if_changed(&id, 100, &api).await.unwrap();
+        assert!(unchanged.is_not_modified());
+        let changed = Sitelinks::get_if_changed(&id, 99, &api).await.unwrap();
+        assert!(changed.is_modified());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_get_if_changed_reports_real_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        let api = api(&mock_server).await;
+        let result = Item::get_if_changed(EntityId::item("Q42"), 100, &api).await;
+        assert!(result.unwrap_err().is_not_found());
+    }
+}
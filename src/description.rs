@@ -1,7 +1,9 @@
 impl_language_string_value!(
     Description,
-    "descriptions",
-    "descriptions_with_language_fallback",
+    GetItemDescription,
+    GetPropertyDescription,
+    GetItemDescriptionWithFallback,
+    GetPropertyDescriptionWithFallback,
     "description",
     "Description"
 );
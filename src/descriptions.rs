@@ -1,6 +1,7 @@
 impl_language_string_collection!(
     Descriptions,
-    "descriptions",
+    GetItemDescriptions,
+    GetPropertyDescriptions,
     "Descriptions",
     descriptions_from_json
 );
@@ -1,4 +1,9 @@
-impl_language_string_patch!(DescriptionsPatch, "descriptions", "DescriptionsPatch");
+impl_language_string_patch!(
+    DescriptionsPatch,
+    PatchItemDescriptions,
+    PatchPropertyDescriptions,
+    "DescriptionsPatch"
+);
 
 #[cfg(test)]
 mod tests {
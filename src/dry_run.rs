@@ -0,0 +1,273 @@
+use crate::{trace, EditMetadata, RestApiError};
+use serde_json::Value;
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+};
+
+/// A write request that was captured instead of sent, because of dry-run mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedWrite {
+    method: reqwest::Method,
+    path: String,
+    body: Option<Value>,
+    edit_metadata: Option<EditMetadata>,
+}
+
+impl PlannedWrite {
+    fn from_request(request: &reqwest::Request, em: Option<&EditMetadata>) -> Self {
+        let url = request.url();
+        let path = url.query().map_or_else(
+            || url.path().to_string(),
+            |query| format!("{}?{query}", url.path()),
+        );
+        Self {
+            method: request.method().clone(),
+            path,
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .and_then(|bytes| serde_json::from_slice(bytes).ok()),
+            edit_metadata: em.cloned(),
+        }
+    }
+
+    /// Returns the HTTP method (POST, PUT, PATCH or DELETE).
+    pub const fn method(&self) -> &reqwest::Method {
+        &self.method
+    }
+
+    /// Returns the URL path, including the query string if there is one.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the JSON body, if the request had one.
+    pub const fn body(&self) -> Option<&Value> {
+        self.body.as_ref()
+    }
+
+    /// Returns the edit metadata, if the write was built with it (see `with_edit_metadata`).
+    pub const fn edit_metadata(&self) -> Option<&EditMetadata> {
+        self.edit_metadata.as_ref()
+    }
+}
+
+impl Display for PlannedWrite {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        if let Some(body) = &self.body {
+            write!(f, " {body}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` on the extensions of `request`, which `reqwest` only exposes through a conversion to
+/// `http::Request`.
+fn with_extensions<T>(
+    mut request: reqwest::Request,
+    f: impl FnOnce(&mut http::Extensions) -> T,
+) -> Result<(reqwest::Request, T), RestApiError> {
+    // The conversion gives a request without a body an empty one, so the body is kept aside.
+    let body = request.body_mut().take();
+    let mut http_request = http::Request::try_from(request)?;
+    let value = f(http_request.extensions_mut());
+    let mut rebuilt = reqwest::Request::try_from(http_request)?;
+    *rebuilt.body_mut() = body;
+    Ok((rebuilt, value))
+}
+
+/// Attaches the edit metadata a write request was built with, so `RestApi::execute` can honour
+/// `EditMetadata::dry_run` and report it in the `PlannedWrite`.
+pub(crate) fn with_edit_metadata(
+    request: reqwest::Request,
+    em: &EditMetadata,
+) -> Result<reqwest::Request, RestApiError> {
+    with_extensions(request, |extensions| {
+        extensions.insert(em.clone());
+    })
+    .map(|(request, ())| request)
+}
+
+/// The dry-run setting of a `RestApi`, and the writes it captured. Shared by all clones.
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    enabled: bool,
+    writes: Mutex<Vec<PlannedWrite>>,
+}
+
+impl Plan {
+    pub(crate) const fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            writes: Mutex::new(vec![]),
+        }
+    }
+
+    pub(crate) const fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Captures `request` if dry-run is on for the `RestApi` or for this call (from the edit
+    /// metadata attached by `with_edit_metadata`), and returns the resulting
+    /// `RestApiError::DryRun`. Otherwise, returns the request to be sent. GET requests are
+    /// never captured.
+    pub(crate) fn capture(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Request, RestApiError> {
+        if request.method() == reqwest::Method::GET {
+            return Ok(request);
+        }
+        let (request, em) = with_extensions(request, |extensions| {
+            extensions.get::<EditMetadata>().cloned()
+        })?;
+        let per_call = em.as_ref().is_some_and(EditMetadata::dry_run);
+        if !(self.enabled || per_call) {
+            return Ok(request);
+        }
+        let write = PlannedWrite::from_request(&request, em.as_ref());
+        trace::planned_write(&write);
+        if let Ok(mut writes) = self.writes.lock() {
+            writes.push(write.clone());
+        }
+        Err(RestApiError::DryRun(Box::new(write)))
+    }
+
+    pub(crate) fn writes(&self) -> Vec<PlannedWrite> {
+        self.writes
+            .lock()
+            .map(|writes| writes.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn take_writes(&self) -> Vec<PlannedWrite> {
+        self.writes
+            .lock()
+            .map(|mut writes| std::mem::take(&mut *writes))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::Entity, label::Label, sitelinks_patch::SitelinksPatch, EntityId, HttpGet, HttpMisc,
+        HttpPut, Item, PatchApply, RestApi, Sitelinks,
+    };
+    use serde_json::json;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn server() -> MockServer {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!("Douglas Adams")))
+            .mount(&mock_server)
+            .await;
+        // Nothing but GETs may reach the server.
+        for verb in ["POST", "PUT", "PATCH", "DELETE"] {
+            Mock::given(method(verb))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(0)
+                .mount(&mock_server)
+                .await;
+        }
+        mock_server
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_dry_run_api() {
+        let mock_server = server().await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .dry_run(true)
+            .build()
+            .unwrap();
+        assert!(api.is_dry_run());
+        let id = EntityId::item("Q42");
+
+        // Reads still go through.
+        let label = Label::get(&id, "en", &api).await.unwrap();
+        assert_eq!(label.value(), "Douglas Adams");
+
+        let mut em = EditMetadata::default();
+        em.set_comment(Some("fix label".to_string()));
+        let result = label.put_meta(&id, &api, em.clone()).await;
+        let Err(RestApiError::DryRun(write)) = result else {
+            panic!("expected a dry run, got {result:?}");
+        };
+        assert_eq!(write.method(), reqwest::Method::PUT);
+        assert_eq!(
+            write.path(),
+            "/w/rest.php/wikibase/v1/entities/items/Q42/labels/en"
+        );
+        assert_eq!(write.body().unwrap()["label"], "Douglas Adams");
+        assert_eq!(write.body().unwrap()["comment"], "fix label");
+        assert_eq!(write.edit_metadata(), Some(&em));
+
+        let posted = Item::default().post(&api).await;
+        assert!(posted.is_err_and(|e| e.is_dry_run()));
+
+        let mut patch = SitelinksPatch::default();
+        patch.replace_title("enwiki", "Douglas Adams");
+        let patched: Result<Sitelinks, _> = patch.apply(&id, &api).await;
+        assert!(patched.is_err_and(|e| e.is_dry_run()));
+
+        let writes = api.take_planned_writes();
+        let methods: Vec<_> = writes.iter().map(|w| w.method().as_str()).collect();
+        assert_eq!(methods, vec!["PUT", "POST", "PATCH"]);
+        assert!(api.planned_writes().is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_dry_run_per_call() {
+        let mock_server = server().await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(!api.is_dry_run());
+        let mut em = EditMetadata::default();
+        em.set_dry_run(true);
+        let label = Label::new("en", "Douglas Adams");
+        let result = label.put_meta(&EntityId::item("Q42"), &api, em).await;
+        assert!(result.is_err_and(|e| e.is_dry_run()));
+        assert_eq!(api.planned_writes().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_dry_run_captures_on_execute() {
+        let mock_server = server().await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut em = EditMetadata::default();
+        em.set_dry_run(true);
+        let label = Label::new("en", "Douglas Adams");
+        let request = label
+            .generate_json_request(
+                &EntityId::item("Q42"),
+                reqwest::Method::PUT,
+                json!({"label": "Douglas Adams"}),
+                &api,
+                &em,
+            )
+            .await
+            .unwrap();
+        // Building a request does not capture it.
+        assert!(api.planned_writes().is_empty());
+        let result = api.execute(request).await;
+        let Err(RestApiError::DryRun(write)) = result else {
+            panic!("expected a dry run, got {result:?}");
+        };
+        assert_eq!(write.edit_metadata(), Some(&em));
+        assert_eq!(api.planned_writes(), vec![*write]);
+    }
+}
@@ -7,6 +7,7 @@ pub struct EditMetadata {
     minor: bool,
     tags: Vec<String>,
     revision_match: RevisionMatch,
+    dry_run: bool,
 }
 
 impl EditMetadata {
@@ -30,6 +31,12 @@ impl EditMetadata {
         &self.revision_match
     }
 
+    /// Returns `true` if this write should be captured instead of sent, see
+    /// `RestApiBuilder::dry_run`.
+    pub const fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     pub fn set_comment(&mut self, comment: Option<String>) {
         self.comment = comment;
     }
@@ -49,6 +56,19 @@ impl EditMetadata {
     pub fn set_revision_match(&mut self, revision_match: RevisionMatch) {
         self.revision_match = revision_match;
     }
+
+    /// Guards the write with `If-Match` on `revision`, the revision the edit is based on. The
+    /// write then fails with 412 if the entity changed since, and can be retried safely after a
+    /// 5xx error, see `RestApiBuilder::with_max_retries`.
+    pub fn set_base_revision(&mut self, revision: u64) {
+        self.revision_match
+            .set_unmodified_since_revisions(vec![revision]);
+    }
+
+    /// Captures this write instead of sending it, even if the `RestApi` is not in dry-run mode.
+    pub const fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
 }
 
 #[cfg(test)]
@@ -74,6 +94,10 @@ mod tests {
 
         edit_metadata.set_tags(vec!["Test".to_string()]);
         assert_eq!(edit_metadata.tags(), &["Test".to_string()]);
+
+        assert!(!edit_metadata.dry_run());
+        edit_metadata.set_dry_run(true);
+        assert!(edit_metadata.dry_run());
     }
 
     #[test]
@@ -84,4 +108,14 @@ mod tests {
         edit_metadata.set_revision_match(revision_match.clone());
         assert_eq!(edit_metadata.revision_match(), &revision_match);
     }
+
+    #[test]
+    fn test_set_base_revision() {
+        let mut edit_metadata = EditMetadata::default();
+        edit_metadata.set_base_revision(1234);
+        assert_eq!(
+            edit_metadata.revision_match().unmodified_since_revisions(),
+            &[1234]
+        );
+    }
 }
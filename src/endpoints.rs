@@ -0,0 +1,265 @@
+//! Typed paths for every operation of the Wikibase REST API, generated at build time from the
+//! bundled `spec/openapi.json` (see `build.rs`).
+//!
+//! Each operation has a parameter struct named after its `operationId`, e.g. `GetItemLabel` for
+//! `getItemLabel`, whose `Endpoint::path` renders the path relative to the versioned API root.
+//! Path parameters are validated as in the rest of the crate: entity IDs must be of the right
+//! type, language codes go through `Language::validated` and site IDs through
+//! `SiteId::validated`, so no parameter can inject extra path segments.
+//!
+//! The model types (`Item`, `Labels`, `Statement`, ...) build their requests from these structs.
+//! `Operation::implemented_by` maps every operation to the type (or method) that implements it,
+//! by reference, so renaming one breaks the build; when the spec gains an operation, that match
+//! no longer compiles until the new endpoint is covered.
+
+use crate::{EntityId, Language, RestApi, RestApiError, SiteId};
+use std::collections::HashMap;
+
+include!(concat!(env!("OUT_DIR"), "/endpoints.rs"));
+
+/// A Wikibase REST API operation with its parameters.
+pub trait Endpoint {
+    /// The operation this endpoint belongs to.
+    const OPERATION: Operation;
+
+    /// Returns the path relative to the versioned API root, e.g. `/entities/items/Q42`.
+    /// # Errors
+    /// Returns an error if a path parameter is invalid.
+    fn path(&self) -> Result<String, RestApiError>;
+
+    /// Returns the query parameters.
+    fn query(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+}
+
+impl Operation {
+    /// Returns the type (or method) of this crate that implements the operation, e.g.
+    /// `wikibase_rest_api::item::Item`.
+    pub fn implemented_by(self) -> &'static str {
+        use crate::{
+            aliases::Aliases, aliases_in_language::AliasesInLanguage, aliases_patch::AliasesPatch,
+            description::Description, descriptions::Descriptions,
+            descriptions_patch::DescriptionsPatch, label::Label, labels::Labels,
+            labels_patch::LabelsPatch, search::Search, sitelinks_patch::SitelinksPatch,
+            statement_patch::StatementPatch, statements::Statements, Item, Property, Sitelink,
+            Sitelinks, Statement,
+        };
+        use std::any::{type_name, type_name_of_val};
+
+        match self {
+            Operation::GetOpenApiDoc => type_name_of_val(&RestApi::get_openapi_json),
+            Operation::GetPropertyDataTypes => type_name_of_val(&RestApi::get_property_data_types),
+            Operation::AddItem | Operation::GetItem | Operation::PatchItem => type_name::<Item>(),
+            Operation::AddProperty | Operation::GetProperty | Operation::PatchProperty => {
+                type_name::<Property>()
+            }
+            Operation::GetSitelinks => type_name::<Sitelinks>(),
+            Operation::PatchSitelinks => type_name::<SitelinksPatch>(),
+            Operation::GetSitelink | Operation::SetSitelink | Operation::DeleteSitelink => {
+                type_name::<Sitelink>()
+            }
+            Operation::GetItemLabels | Operation::GetPropertyLabels => type_name::<Labels>(),
+            Operation::PatchItemLabels | Operation::PatchPropertyLabels => {
+                type_name::<LabelsPatch>()
+            }
+            Operation::GetItemLabel
+            | Operation::ReplaceItemLabel
+            | Operation::DeleteItemLabel
+            | Operation::GetItemLabelWithFallback
+            | Operation::GetPropertyLabel
+            | Operation::ReplacePropertyLabel
+            | Operation::DeletePropertyLabel
+            | Operation::GetPropertyLabelWithFallback => type_name::<Label>(),
+            Operation::GetItemDescriptions | Operation::GetPropertyDescriptions => {
+                type_name::<Descriptions>()
+            }
+            Operation::PatchItemDescriptions | Operation::PatchPropertyDescriptions => {
+                type_name::<DescriptionsPatch>()
+            }
+            Operation::GetItemDescription
+            | Operation::ReplaceItemDescription
+            | Operation::DeleteItemDescription
+            | Operation::GetItemDescriptionWithFallback
+            | Operation::GetPropertyDescription
+            | Operation::SetPropertyDescription
+            | Operation::DeletePropertyDescription
+            | Operation::GetPropertyDescriptionWithFallback => type_name::<Description>(),
+            Operation::GetItemAliases | Operation::GetPropertyAliases => type_name::<Aliases>(),
+            Operation::PatchItemAliases | Operation::PatchPropertyAliases => {
+                type_name::<AliasesPatch>()
+            }
+            Operation::GetItemAliasesInLanguage
+            | Operation::AddItemAliasesInLanguage
+            | Operation::GetPropertyAliasesInLanguage
+            | Operation::AddPropertyAliasesInLanguage => type_name::<AliasesInLanguage>(),
+            Operation::GetItemStatements
+            | Operation::AddItemStatement
+            | Operation::GetPropertyStatements
+            | Operation::AddPropertyStatement => type_name::<Statements>(),
+            Operation::GetItemStatement
+            | Operation::ReplaceItemStatement
+            | Operation::DeleteItemStatement
+            | Operation::GetPropertyStatement
+            | Operation::ReplacePropertyStatement
+            | Operation::DeletePropertyStatement
+            | Operation::GetStatement
+            | Operation::ReplaceStatement
+            | Operation::DeleteStatement => type_name::<Statement>(),
+            Operation::PatchItemStatement
+            | Operation::PatchPropertyStatement
+            | Operation::PatchStatement => type_name::<StatementPatch>(),
+            Operation::SearchItem => type_name::<Search>(),
+        }
+    }
+}
+
+/// Returns the ID for an entity path parameter, which must be in `group`.
+fn entity_id<'a>(id: &'a EntityId, group: &str, field: &str) -> Result<&'a str, RestApiError> {
+    if id.group()? != group {
+        return Err(RestApiError::WrongType {
+            field: field.to_string(),
+            j: id.to_string().into(),
+        });
+    }
+    Ok(id.id()?)
+}
+
+impl RestApi {
+    /// Returns a `RequestBuilder` for an endpoint, with its path and query parameters.
+    /// # Errors
+    /// Returns an error if a path parameter is invalid, or if the headers cannot be created.
+    pub async fn endpoint_request_builder<E: Endpoint>(
+        &self,
+        endpoint: &E,
+    ) -> Result<reqwest::RequestBuilder, RestApiError> {
+        self.wikibase_request_builder(endpoint.path()?, endpoint.query(), E::OPERATION.method())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::Capabilities;
+    use serde_json::Value;
+
+    #[test]
+    fn test_paths() {
+        let q42 = EntityId::item("Q42");
+        let p31 = EntityId::property("P31");
+        assert_eq!(
+            GetItem {
+                item_id: &q42,
+                fields: None
+            }
+            .path()
+            .unwrap(),
+            "/entities/items/Q42"
+        );
+        let label = GetPropertyLabel {
+            property_id: &p31,
+            language_code: " DE ",
+        };
+        assert_eq!(label.path().unwrap(), "/entities/properties/P31/labels/de");
+        let sitelink = GetSitelink {
+            item_id: &q42,
+            site_id: "enwiki",
+        };
+        assert_eq!(
+            sitelink.path().unwrap(),
+            "/entities/items/Q42/sitelinks/enwiki"
+        );
+        assert_eq!(GetOpenApiDoc.path().unwrap(), "/openapi.json");
+        assert_eq!(
+            SearchItem {
+                q: "Douglas Adams",
+                language: "en"
+            }
+            .path()
+            .unwrap(),
+            "/search/items"
+        );
+    }
+
+    #[test]
+    fn test_path_validation() {
+        let q42 = EntityId::item("Q42");
+        let injected = GetItemLabel {
+            item_id: &q42,
+            language_code: "en/../../x",
+        };
+        assert!(matches!(
+            injected.path(),
+            Err(RestApiError::InvalidLanguageCode(_))
+        ));
+        let sitelink = DeleteSitelink {
+            item_id: &q42,
+            site_id: "enwiki?x",
+        };
+        assert!(matches!(
+            sitelink.path(),
+            Err(RestApiError::InvalidSiteId(_))
+        ));
+        let wrong_type = GetItem {
+            item_id: &EntityId::property("P31"),
+            fields: None,
+        };
+        assert!(matches!(
+            wrong_type.path(),
+            Err(RestApiError::WrongType { .. })
+        ));
+        let none = GetItem {
+            item_id: &EntityId::None,
+            fields: None,
+        };
+        assert!(matches!(none.path(), Err(RestApiError::IsNone)));
+    }
+
+    #[test]
+    fn test_query() {
+        let q42 = EntityId::item("Q42");
+        let query = GetItem {
+            item_id: &q42,
+            fields: Some("labels,statements"),
+        }
+        .query();
+        assert_eq!(query.get("_fields").unwrap(), "labels,statements");
+        let search = SearchItem {
+            q: "Douglas Adams",
+            language: "en",
+        }
+        .query();
+        assert_eq!(search.len(), 2);
+        assert_eq!(search.get("q").unwrap(), "Douglas Adams");
+    }
+
+    #[test]
+    fn test_operations_match_spec() {
+        let spec: Value =
+            serde_json::from_str(&std::fs::read_to_string("spec/openapi.json").unwrap()).unwrap();
+        let capabilities = Capabilities::from_openapi_json(&spec).unwrap();
+        let operation_count: usize = capabilities
+            .paths()
+            .map(|path| capabilities.methods(path).len())
+            .sum();
+        assert_eq!(Operation::ALL.len(), operation_count);
+        for operation in Operation::ALL {
+            assert!(capabilities
+                .methods(operation.path_template())
+                .contains(&operation.method()));
+            assert!(operation
+                .implemented_by()
+                .starts_with("wikibase_rest_api::"));
+        }
+        assert_eq!(Operation::GetItemLabel.operation_id(), "getItemLabel");
+        assert_eq!(
+            Operation::GetItemLabel.implemented_by(),
+            "wikibase_rest_api::label::Label"
+        );
+        assert_eq!(
+            GetItemLabel::OPERATION.path_template(),
+            "/v1/entities/items/{item_id}/labels/{language_code}"
+        );
+    }
+}
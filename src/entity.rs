@@ -1,4 +1,9 @@
-use crate::{EditMetadata, EntityId, HeaderInfo, HttpMisc, RestApi, RestApiError, RevisionMatch};
+use crate::{
+    conditional::Conditional,
+    endpoints::{AddItem, AddProperty, Endpoint, GetItem, GetProperty},
+    trace, EditMetadata, EntityId, HeaderInfo, HttpMisc, RestApi, RestApiError, RetryContext,
+    RevisionMatch,
+};
 use reqwest::{Request, Response};
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -57,19 +62,36 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
         rm: RevisionMatch,
         fields: &[&str],
     ) -> Result<Request, RestApiError> {
-        let path = format!("/entities/{group}/{id}", group = id.group()?);
-        let mut params = HashMap::new();
-        if !fields.is_empty() {
-            params.insert("_fields".to_string(), fields.join(","));
-        }
-        let mut request = api
-            .wikibase_request_builder(&path, params, reqwest::Method::GET)
-            .await?
-            .build()?;
+        let fields = (!fields.is_empty()).then(|| fields.join(","));
+        let fields = fields.as_deref();
+        let builder = match id {
+            EntityId::Property(_) => {
+                let property_id = &id;
+                api.endpoint_request_builder(&GetProperty {
+                    property_id,
+                    fields,
+                })
+                .await?
+            }
+            _ => {
+                let item_id = &id;
+                api.endpoint_request_builder(&GetItem { item_id, fields })
+                    .await?
+            }
+        };
+        let mut request = builder.build()?;
         rm.modify_headers(request.headers_mut())?;
         Ok(request)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "entity.get",
+            skip_all,
+            fields(entity_id = %id, revision_id = tracing::field::Empty)
+        )
+    )]
     async fn get_match(
         id: EntityId,
         api: &RestApi,
@@ -78,9 +100,21 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
         let request = Self::generate_get_match_request(id, api, rm).await?;
         let response = api.execute(request).await?;
         let (j, hi) = Self::parse_response(response).await?;
+        trace::record_revision_id(&hi);
         Self::from_json_header_info(j, hi)
     }
 
+    /// Gets the entity only if it has changed since `known_revision`.
+    /// Returns `Conditional::NotModified` if the server answers 304.
+    async fn get_if_changed(
+        id: EntityId,
+        known_revision: u64,
+        api: &RestApi,
+    ) -> Result<Conditional<Self>, RestApiError> {
+        let rm = Conditional::<Self>::revision_match(known_revision);
+        Conditional::from_result(Self::get_match(id, api, rm).await)
+    }
+
     async fn get_fields(
         id: EntityId,
         fields: &[&str],
@@ -126,7 +160,8 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
         let mut j: Value = json!({entity_type.type_name(): self});
         Self::add_metadata_to_json(&mut j, &em);
         *request.body_mut() = Some(format!("{j}").into());
-        Ok(request)
+        api.check_request_schema(&request)?;
+        crate::dry_run::with_edit_metadata(request, &em)
     }
 
     async fn check_post_with_type_and_metadata_response(
@@ -146,6 +181,14 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
         Err(RestApiError::from_response(response).await)
     }
 
+    /// Creates the entity.
+    ///
+    /// A POST is not retried blindly on a 5xx error, as the server may have created the entity
+    /// anyway. Wikibase rejects a second item with the same label and description in a
+    /// language, and a second property with the same label, so such an entity is posted once
+    /// more, if the `RetryPolicy` allows: if the first attempt was committed, the second is
+    /// rejected, and the original error is returned. Which entity the first attempt created
+    /// cannot be told reliably, so the caller has to check for it.
     async fn post_with_type_and_metadata(
         &self,
         entity_type: EntityType,
@@ -155,12 +198,50 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
         if self.id().is_some() {
             return Err(RestApiError::HasId);
         }
-        let path = format!("/entities/{group}", group = entity_type.group_name());
+        let path = match entity_type {
+            EntityType::Item => AddItem.path()?,
+            EntityType::Property => AddProperty.path()?,
+        };
         let request = self
             .build_post_with_type_and_metadata_request(entity_type, &path, api, em)
             .await?;
+        let retry = request.try_clone();
+        let result = Self::send_post_request(&path, request, api).await;
+        let (
+            Err(RestApiError::ApiError {
+                status, payload, ..
+            }),
+            Some(retry),
+        ) = (&result, retry)
+        else {
+            return result;
+        };
+        if !status.is_server_error() {
+            return result;
+        }
+        let context = RetryContext::new(&reqwest::Method::POST, *status)
+            .with_error_code(Some(payload.code()))
+            .with_safe_to_repeat(true);
+        let Some(delay) = api.retry_policy().retry_delay(&context) else {
+            return result;
+        };
+        let posted = serde_json::to_value(self)?;
+        if !rejects_duplicate(entity_type, &posted) {
+            return result;
+        }
+        api.stats.record_retry(delay);
+        tokio::time::sleep(delay).await;
+        // If the repost fails, e.g. as a duplicate of the first attempt, the original error stands.
+        Self::send_post_request(&path, retry, api).await.or(result)
+    }
+
+    async fn send_post_request(
+        path: &str,
+        request: Request,
+        api: &RestApi,
+    ) -> Result<Self, RestApiError> {
         let response = api.execute(request).await?;
-        let response = Self::check_post_with_type_and_metadata_response(&path, response).await?;
+        let response = Self::check_post_with_type_and_metadata_response(path, response).await?;
 
         let j: Value = response.json().await?;
         // TODO return entire entity? Check if it's the same as this one?
@@ -169,10 +250,24 @@ pub trait Entity: Default + Sized + Serialize + HttpMisc {
     }
 }
 
+/// Returns `true` if Wikibase would reject a second entity like `posted`: an item with the
+/// same label and description in a language, or a property with the same label.
+fn rejects_duplicate(entity_type: EntityType, posted: &Value) -> bool {
+    let Some(labels) = posted["labels"].as_object() else {
+        return false;
+    };
+    match entity_type {
+        EntityType::Item => labels
+            .keys()
+            .any(|language| posted["descriptions"].get(language).is_some()),
+        EntityType::Property => !labels.is_empty(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{item::Item, RestApi};
+    use crate::{item::Item, language_strings::LanguageStrings, RestApi};
     use serde_json::json;
     use wiremock::matchers::{method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -285,4 +380,91 @@ mod tests {
             other => panic!("Wrong error type: {other:?}"),
         }
     }
+
+    /// Posts an item labelled and described "Douglas Adams"/"writer" (without a description
+    /// if `described` is false), whose first POST fails with 503. A second POST is rejected as
+    /// a duplicate of Q123 if `duplicate`, and creates Q123 otherwise.
+    async fn post_item_after_server_error(
+        described: bool,
+        duplicate: bool,
+    ) -> (MockServer, Result<Item, RestApiError>) {
+        let mock_server = MockServer::start().await;
+        let posts_path = "/w/rest.php/wikibase/v1/entities/items";
+        Mock::given(method("POST"))
+            .and(path(posts_path))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let repost = if duplicate {
+            ResponseTemplate::new(422).set_body_json(json!({
+                "code": "data-policy-violation",
+                "message": "Edit violates data policy",
+                "context": {
+                    "violation": "item-label-description-duplicate",
+                    "violation_context": {"language": "en", "conflicting_item_id": "Q123"},
+                },
+            }))
+        } else {
+            ResponseTemplate::new(201).set_body_json(json!({
+                "id": "Q123",
+                "type": "item",
+                "labels": {"en": "Douglas Adams"},
+                "descriptions": {"en": "writer"},
+            }))
+        };
+        Mock::given(method("POST"))
+            .and(path(posts_path))
+            .respond_with(repost)
+            .expect(u64::from(described))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_retry_base_delay(std::time::Duration::from_millis(1))
+            .build()
+            .unwrap();
+        let mut item = Item::default();
+        item.labels_mut()
+            .insert(crate::LanguageString::new("en", "Douglas Adams"));
+        if described {
+            item.descriptions_mut()
+                .insert(crate::LanguageString::new("en", "writer"));
+        }
+        let posted = item.post(&api).await;
+        (mock_server, posted)
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_post_reposted_after_server_error() {
+        let (mock_server, posted) = post_item_after_server_error(true, false).await;
+        assert_eq!(posted.unwrap().id(), &EntityId::item("Q123"));
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_post_duplicate_after_server_error_returns_original_error() {
+        // The first attempt may have created the duplicate, or someone else may have; either
+        // way, the server error is returned, and nothing is read to guess which.
+        let (mock_server, posted) = post_item_after_server_error(true, true).await;
+        match posted {
+            Err(RestApiError::ApiError { status, .. }) => assert_eq!(status, 503),
+            other => panic!("Wrong result: {other:?}"),
+        }
+        let received = mock_server.received_requests().await.unwrap();
+        assert!(received.iter().all(|r| r.method.as_str() == "POST"));
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_post_not_reposted_without_duplicate_check() {
+        // Without a description, Wikibase would accept a second item, so it is not reposted.
+        let (mock_server, posted) = post_item_after_server_error(false, true).await;
+        assert!(matches!(posted, Err(RestApiError::ApiError { .. })));
+        mock_server.verify().await;
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{entity::Entity, EntityId, Item, Property, RestApi, RestApiError};
+use crate::{entity::Entity, trace, EntityId, Item, Property, RestApi, RestApiError};
 use futures::prelude::*;
 use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::sync::RwLock;
@@ -82,6 +82,19 @@ impl EntityContainer {
     /// triggers a re-sweep of only the affected IDs, at halved concurrency, up to
     /// `container_retries` times; other failures are reported as-is (the request layer
     /// has already retried them).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "entity_container.load",
+            skip_all,
+            fields(
+                entities = entity_ids.len(),
+                loaded = tracing::field::Empty,
+                missing = tracing::field::Empty,
+                failed = tracing::field::Empty,
+            )
+        )
+    )]
     pub async fn load_report(&self, entity_ids: &[EntityId]) -> LoadReport {
         let item_ids = {
             let items = self.items.read().await;
@@ -99,6 +112,11 @@ impl EntityContainer {
         )
         .await;
         report.merge(properties_report);
+        trace::record_load(
+            report.loaded.len(),
+            report.missing.len(),
+            report.failed.len(),
+        );
         report
     }
 
@@ -174,6 +192,7 @@ impl EntityContainer {
             if ids.is_empty() {
                 break;
             }
+            trace::sweep("item", round, ids.len(), concurrency);
             let outcomes = self.fetch_items(&ids, concurrency).await;
             let (loaded, rate_limited) =
                 self.classify_round(outcomes, round, EntityId::Item, &mut report);
@@ -201,6 +220,7 @@ impl EntityContainer {
             if ids.is_empty() {
                 break;
             }
+            trace::sweep("property", round, ids.len(), concurrency);
             let outcomes = self.fetch_properties(&ids, concurrency).await;
             let (loaded, rate_limited) =
                 self.classify_round(outcomes, round, EntityId::Property, &mut report);
@@ -246,9 +266,13 @@ impl EntityContainer {
     }
 
     /// Waits out the rate limit and halves concurrency before re-sweeping the failed IDs.
+    /// If the `RestApi` has adaptive concurrency, that controller already throttles (and later
+    /// recovers) the number of in-flight requests, so the local setting is left alone.
     async fn prepare_resweep(&self, ids: Vec<String>, concurrency: &mut usize) -> Vec<String> {
         tokio::time::sleep(self.container_backoff).await;
-        *concurrency = (*concurrency / 2).max(1);
+        if self.api.concurrency_limit().is_none() {
+            *concurrency = (*concurrency / 2).max(1);
+        }
         ids
     }
 
@@ -1,5 +1,6 @@
 /// NOTE: THIS IS INCOMPLETE AND UNTESTED!
 use crate::{
+    endpoints::{Endpoint, PatchItem, PatchProperty},
     entity::{Entity, EntityType},
     patch_entry::PatchEntry,
     EditMetadata, EntityId, HttpMisc, Item, Property, RestApi, RestApiError,
@@ -94,11 +95,10 @@ impl EntityPatch {
 
 impl HttpMisc for EntityPatch {
     fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!(
-            "/entities/{group}/{id}/{mode}",
-            group = id.group()?,
-            mode = self.mode.as_str()
-        ))
+        match self.mode {
+            EntityType::Item => PatchItem { item_id: id }.path(),
+            EntityType::Property => PatchProperty { property_id: id }.path(),
+        }
     }
 }
 
@@ -119,8 +119,12 @@ mod tests {
         let id = EntityId::new("Q123").unwrap();
         assert_eq!(
             patch.get_my_rest_api_path(&id).unwrap(),
-            "/entities/items/Q123/item"
+            "/entities/items/Q123"
         );
+        // The patch is for an item, so it cannot apply to a property.
+        assert!(patch
+            .get_my_rest_api_path(&EntityId::property("P31"))
+            .is_err());
     }
 
     #[test]
@@ -168,7 +172,7 @@ mod tests {
 
         let mock_server = MockServer::start().await;
         Mock::given(method("PATCH"))
-            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42/item"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
             .respond_with(ResponseTemplate::new(200).set_body_json(&v))
             .mount(&mock_server)
             .await;
@@ -208,9 +212,7 @@ mod tests {
 
         let mock_server = MockServer::start().await;
         Mock::given(method("PATCH"))
-            .and(path(
-                "/w/rest.php/wikibase/v1/entities/properties/P214/property",
-            ))
+            .and(path("/w/rest.php/wikibase/v1/entities/properties/P214"))
             .respond_with(ResponseTemplate::new(200).set_body_json(&v))
             .mount(&mock_server)
             .await;
@@ -1,3 +1,5 @@
+use crate::dry_run::PlannedWrite;
+use crate::schema_validation::SchemaReport;
 use reqwest::header::InvalidHeaderValue;
 use serde::Deserialize;
 use serde_json::{json, Value};
@@ -9,7 +11,10 @@ use thiserror::Error;
 
 #[derive(Debug, Clone, Deserialize, Default, PartialEq)]
 pub struct RestApiErrorPayload {
+    /// `error` in `OAuth2` error responses (RFC 6749).
+    #[serde(alias = "error")]
     code: String,
+    #[serde(default)]
     message: String,
     #[serde(default)]
     context: HashMap<String, Value>,
@@ -57,6 +62,18 @@ pub enum RestApiError {
     RefreshTokenRequired,
     #[error("Access token required")]
     AccessTokenRequired,
+    #[error("PKCE code verifier required; start the flow with start_authorization")]
+    CodeVerifierRequired,
+    #[error("OAuth2 state mismatch")]
+    OAuth2StateMismatch,
+    #[error("No randomness available: {0}")]
+    Randomness(std::io::Error),
+    #[error("Bot password required")]
+    BotPasswordRequired,
+    #[error("Login failed: {0}")]
+    LoginFailed(String),
+    #[error("Authorization denied: {0}")]
+    AuthorizationDenied(String),
     #[error("Reqwest Error: {0}")]
     Reqwest(reqwest::Error),
     #[error("Invalid header value: {0}")]
@@ -108,6 +125,28 @@ pub enum RestApiError {
     MissingResults,
     #[error("REST API path not implemented: {0}")]
     PathNotImplemented(String),
+    #[error("Request rejected by middleware: {0}")]
+    RequestRejected(String),
+    #[error("Cassette: {0}")]
+    Cassette(String),
+    #[error("Token store: {0}")]
+    TokenStore(String),
+    #[error("Runtime error: {0}")]
+    Runtime(std::io::Error),
+    #[error("Loopback listener error: {0}")]
+    Loopback(std::io::Error),
+    #[error("Dry run, not sent: {0}")]
+    DryRun(Box<PlannedWrite>),
+    #[error("Circuit breaker open, next probe in {0:?}")]
+    CircuitOpen(std::time::Duration),
+    #[error("Shared request failed: {message}")]
+    SharedRequestFailed {
+        message: String,
+        timeout: bool,
+        connect: bool,
+    },
+    #[error("Schema violation in {0}")]
+    SchemaViolations(Box<SchemaReport>),
 }
 
 impl From<reqwest::Error> for RestApiError {
@@ -147,6 +186,93 @@ impl RestApiError {
         )
     }
 
+    /// Returns `true` if this is an API error with HTTP status 304 (Not Modified), i.e. the
+    /// answer to a conditional request whose known revision is still current.
+    pub const fn is_not_modified(&self) -> bool {
+        matches!(
+            self,
+            RestApiError::ApiError { status, .. }
+                if status.as_u16() == reqwest::StatusCode::NOT_MODIFIED.as_u16()
+        )
+    }
+
+    /// Returns `true` if this is an API error with a 5xx HTTP status. After such an error, a
+    /// write may or may not have been committed.
+    pub const fn is_server_error(&self) -> bool {
+        matches!(
+            self,
+            RestApiError::ApiError { status, .. } if status.as_u16() >= 500 && status.as_u16() < 600
+        )
+    }
+
+    /// Returns `true` if this write was captured by dry-run mode instead of being sent.
+    pub const fn is_dry_run(&self) -> bool {
+        matches!(self, RestApiError::DryRun(_))
+    }
+
+    /// Returns `true` if the request was not sent because the circuit breaker is open.
+    pub const fn is_circuit_open(&self) -> bool {
+        matches!(self, RestApiError::CircuitOpen(_))
+    }
+
+    /// Returns `true` if a payload did not match the `OpenAPI` schema, see
+    /// `RestApiBuilder::with_schema_validation`.
+    pub const fn is_schema_violation(&self) -> bool {
+        matches!(self, RestApiError::SchemaViolations(_))
+    }
+
+    /// Returns `true` if the request timed out, also when this caller shared it with a
+    /// coalesced request.
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            RestApiError::Reqwest(e) => e.is_timeout(),
+            RestApiError::SharedRequestFailed { timeout, .. } => *timeout,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the server could not be connected to, also when this caller shared
+    /// the request with a coalesced request.
+    pub fn is_connect(&self) -> bool {
+        match self {
+            RestApiError::Reqwest(e) => e.is_connect(),
+            RestApiError::SharedRequestFailed { connect, .. } => *connect,
+            _ => false,
+        }
+    }
+
+    /// Returns a copy of this error for the other callers of a coalesced request. API errors,
+    /// open circuits and shared failures are copied as they are; others become
+    /// `SharedRequestFailed`, which keeps `is_timeout` and `is_connect`.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            RestApiError::ApiError {
+                status,
+                status_text,
+                payload,
+            } => RestApiError::ApiError {
+                status: *status,
+                status_text: status_text.clone(),
+                payload: payload.clone(),
+            },
+            RestApiError::CircuitOpen(retry_in) => RestApiError::CircuitOpen(*retry_in),
+            RestApiError::SharedRequestFailed {
+                message,
+                timeout,
+                connect,
+            } => RestApiError::SharedRequestFailed {
+                message: message.clone(),
+                timeout: *timeout,
+                connect: *connect,
+            },
+            e => RestApiError::SharedRequestFailed {
+                message: e.to_string(),
+                timeout: e.is_timeout(),
+                connect: e.is_connect(),
+            },
+        }
+    }
+
     pub async fn from_response(response: reqwest::Response) -> Self {
         let status = response.status();
         let status_text = status.canonical_reason().unwrap_or_default().to_string();
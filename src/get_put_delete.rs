@@ -1,4 +1,7 @@
-use crate::{prelude::RestApiError, EditMetadata, EntityId, HeaderInfo, RestApi, RevisionMatch};
+use crate::{
+    conditional::Conditional, prelude::RestApiError, EditMetadata, EntityId, HeaderInfo, RestApi,
+    RevisionMatch,
+};
 use reqwest::Request;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -103,7 +106,8 @@ pub trait HttpMisc {
             .insert(reqwest::header::CONTENT_TYPE, content_type);
         em.revision_match().modify_headers(request.headers_mut())?;
         *request.body_mut() = Some(format!("{j}").into());
-        Ok(request)
+        api.check_request_schema(&request)?;
+        crate::dry_run::with_edit_metadata(request, em)
     }
 
     async fn filter_response_error(
@@ -126,6 +130,18 @@ pub trait HttpGet: Sized + HttpMisc {
     async fn get(id: &EntityId, part_id: &str, api: &RestApi) -> Result<Self, RestApiError> {
         Self::get_match(id, part_id, api, RevisionMatch::default()).await
     }
+
+    /// Gets the value only if the entity has changed since `known_revision`.
+    /// Returns `Conditional::NotModified` if the server answers 304.
+    async fn get_if_changed(
+        id: &EntityId,
+        part_id: &str,
+        known_revision: u64,
+        api: &RestApi,
+    ) -> Result<Conditional<Self>, RestApiError> {
+        let rm = Conditional::<Self>::revision_match(known_revision);
+        Conditional::from_result(Self::get_match(id, part_id, api, rm).await)
+    }
 }
 
 /// A trait implementing a HTTP PUT operation.
@@ -171,6 +187,17 @@ pub trait HttpGetEntity: Sized + HttpMisc {
     {
         Self::get_match(id, api, RevisionMatch::default()).await
     }
+
+    /// Gets the value only if the entity has changed since `known_revision`.
+    /// Returns `Conditional::NotModified` if the server answers 304.
+    async fn get_if_changed(
+        id: &EntityId,
+        known_revision: u64,
+        api: &RestApi,
+    ) -> Result<Conditional<Self>, RestApiError> {
+        let rm = Conditional::<Self>::revision_match(known_revision);
+        Conditional::from_result(Self::get_match(id, api, rm).await)
+    }
 }
 
 pub trait HttpGetEntityWithFallback: Sized + HttpMisc {
@@ -1,7 +1,9 @@
 impl_language_string_value!(
     Label,
-    "labels",
-    "labels_with_language_fallback",
+    GetItemLabel,
+    GetPropertyLabel,
+    GetItemLabelWithFallback,
+    GetPropertyLabelWithFallback,
     "label",
     "Label"
 );
@@ -1,4 +1,10 @@
-impl_language_string_collection!(Labels, "labels", "Labels", labels_from_json);
+impl_language_string_collection!(
+    Labels,
+    GetItemLabels,
+    GetPropertyLabels,
+    "Labels",
+    labels_from_json
+);
 
 #[cfg(test)]
 mod tests {
@@ -1,4 +1,9 @@
-impl_language_string_patch!(LabelsPatch, "labels", "LabelsPatch");
+impl_language_string_patch!(
+    LabelsPatch,
+    PatchItemLabels,
+    PatchPropertyLabels,
+    "LabelsPatch"
+);
 
 #[cfg(test)]
 mod tests {
@@ -1,6 +1,12 @@
 use crate::{
-    descriptions::Descriptions, labels::Labels, patch_entry::PatchEntry, EditMetadata, EntityId,
-    FromJson, HttpMisc, Patch, PatchApply, RestApi, RestApiError,
+    descriptions::Descriptions,
+    endpoints::{
+        Endpoint, PatchItemDescriptions, PatchItemLabels, PatchPropertyDescriptions,
+        PatchPropertyLabels,
+    },
+    labels::Labels,
+    patch_entry::PatchEntry,
+    EditMetadata, EntityId, FromJson, HttpMisc, Patch, PatchApply, RestApi, RestApiError,
 };
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -11,15 +17,6 @@ enum Mode {
     Descriptions,
 }
 
-impl Mode {
-    const fn as_str(&self) -> &str {
-        match self {
-            Mode::Labels => "labels",
-            Mode::Descriptions => "descriptions",
-        }
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct LanguageStringsPatch {
     patch: Vec<PatchEntry>,
@@ -129,11 +126,14 @@ impl PatchApply<Descriptions> for LanguageStringsPatch {
 
 impl HttpMisc for LanguageStringsPatch {
     fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!(
-            "/entities/{group}/{id}/{mode}",
-            group = id.group()?,
-            mode = self.mode.as_str()
-        ))
+        match (&self.mode, id) {
+            (Mode::Labels, EntityId::Property(_)) => PatchPropertyLabels { property_id: id }.path(),
+            (Mode::Labels, _) => PatchItemLabels { item_id: id }.path(),
+            (Mode::Descriptions, EntityId::Property(_)) => {
+                PatchPropertyDescriptions { property_id: id }.path()
+            }
+            (Mode::Descriptions, _) => PatchItemDescriptions { item_id: id }.path(),
+        }
     }
 }
 
@@ -218,9 +218,20 @@ mod tests {
     }
 
     #[test]
-    fn test_mode_as_str() {
-        assert_eq!(Mode::Labels.as_str(), "labels");
-        assert_eq!(Mode::Descriptions.as_str(), "descriptions");
+    fn test_rest_api_path() {
+        let p31 = EntityId::property("P31");
+        assert_eq!(
+            LanguageStringsPatch::labels()
+                .get_my_rest_api_path(&p31)
+                .unwrap(),
+            "/entities/properties/P31/labels"
+        );
+        assert_eq!(
+            LanguageStringsPatch::descriptions()
+                .get_my_rest_api_path(&EntityId::item("Q42"))
+                .unwrap(),
+            "/entities/items/Q42/descriptions"
+        );
     }
 
     #[test]
@@ -52,12 +52,23 @@ pub mod aliases;
 pub mod aliases_in_language;
 pub mod aliases_patch;
 pub mod bearer_token;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod bot_password;
+pub mod capabilities;
+pub mod cassette;
+pub mod circuit_breaker;
+pub(crate) mod coalesce;
+pub mod concurrency;
+pub mod conditional;
 pub mod config;
 pub mod data_type;
 pub mod description;
 pub mod descriptions;
 pub mod descriptions_patch;
+pub mod dry_run;
 pub mod edit_metadata;
+pub mod endpoints;
 pub mod entity;
 pub mod entity_container;
 pub mod entity_id;
@@ -72,16 +83,24 @@ pub mod labels_patch;
 pub mod language_string;
 pub mod language_strings;
 pub mod language_strings_patch;
+pub mod middleware;
+#[cfg(feature = "oauth-loopback")]
+pub mod oauth_loopback;
 pub mod patch;
 pub(crate) mod patch_entry;
 pub mod prelude;
 pub mod property;
 pub mod property_value;
+pub mod rate_limit;
 pub mod reference;
+pub mod response_cache;
 pub mod rest_api;
 pub mod rest_api_builder;
+pub mod retry;
 pub mod revision_match;
+pub mod schema_validation;
 pub mod search;
+pub mod secret;
 pub mod sitelink;
 pub mod sitelinks;
 pub mod sitelinks_patch;
@@ -92,10 +111,23 @@ pub mod statement_value;
 pub mod statement_value_content;
 pub mod statements;
 pub(crate) mod statements_patch;
+pub mod stats;
+pub mod token_store;
+pub(crate) mod trace;
+pub mod transport;
 
+pub use bearer_token::TokenEvent;
+pub use bot_password::{BotPassword, CookieSession};
+pub use capabilities::Capabilities;
+pub use cassette::{Cassette, CassetteMode};
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use concurrency::AdaptiveConcurrency;
+pub use conditional::Conditional;
 pub use config::Config;
 pub use data_type::DataType;
+pub use dry_run::PlannedWrite;
 pub use edit_metadata::EditMetadata;
+pub use endpoints::{Endpoint, Operation};
 pub use entity_container::{EntityContainer, EntityContainerBuilder, LoadReport};
 pub use entity_id::EntityId;
 pub use error::RestApiError;
@@ -105,12 +137,25 @@ pub use get_put_delete::{
 pub use header_info::HeaderInfo;
 pub use item::Item;
 pub use language_string::{Language, LanguageString};
+pub use middleware::Middleware;
 pub use patch::{FromJson, Patch, PatchApply};
 pub use property::Property;
+pub use rate_limit::RateLimit;
 pub use reference::Reference;
+pub use response_cache::ResponseCache;
 pub use rest_api::RestApi;
+pub use retry::{ExponentialBackoff, FixedDelay, NeverRetry, RetryContext, RetryPolicy};
 pub use revision_match::RevisionMatch;
+pub use schema_validation::{PayloadKind, SchemaReport, SchemaViolation};
+pub use secret::Secret;
 pub use sitelink::{SiteId, Sitelink};
 pub use sitelinks::Sitelinks;
 pub use statement::Statement;
 pub use statement_rank::StatementRank;
+pub use stats::{EndpointGroup, LatencyHistogram, RequestStats};
+pub use token_store::{FileTokenStore, MemoryTokenStore, StoredTokens, TokenStore};
+pub use transport::{ReqwestTransport, Transport};
+
+// Only the `tracing` feature tests use it, for their capturing subscriber.
+#[cfg(all(test, not(feature = "tracing")))]
+use tracing_core as _;
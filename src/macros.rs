@@ -2,19 +2,27 @@
 ///
 /// Parameters:
 /// - `$type_name`: The struct name (e.g., `Label`, `Description`)
-/// - `$path_segment`: REST API path segment (e.g., `"labels"`, `"descriptions"`)
-/// - `$fallback_segment`: Fallback path segment (e.g., `"labels_with_language_fallback"`)
+/// - `$item_endpoint`, `$property_endpoint`: Endpoints of the value of an item or property
+///   (e.g., `GetItemLabel`, `GetPropertyLabel`)
+/// - `$item_fallback_endpoint`, `$property_fallback_endpoint`: Endpoints of the value with
+///   language fallback (e.g., `GetItemLabelWithFallback`)
 /// - `$json_field`: JSON field name for PUT requests (e.g., `"label"`, `"description"`)
 /// - `$error_field`: Field name for error messages (e.g., `"Label"`, `"Description"`)
 macro_rules! impl_language_string_value {
     (
         $type_name:ident,
-        $path_segment:literal,
-        $fallback_segment:literal,
+        $item_endpoint:ident,
+        $property_endpoint:ident,
+        $item_fallback_endpoint:ident,
+        $property_fallback_endpoint:ident,
         $json_field:literal,
         $error_field:literal
     ) => {
         use crate::{
+            endpoints::{
+                Endpoint, $item_endpoint, $item_fallback_endpoint, $property_endpoint,
+                $property_fallback_endpoint,
+            },
             EditMetadata, EntityId, HeaderInfo, HttpDelete, HttpGet, HttpGetEntityWithFallback,
             HttpMisc, HttpPut, Language, LanguageString, RestApi, RestApiError, RevisionMatch,
         };
@@ -40,17 +48,44 @@ macro_rules! impl_language_string_value {
                 }
             }
 
+            /// Returns the path of the value in `language`, optionally with language fallback.
+            fn rest_api_path(
+                id: &EntityId,
+                language: &str,
+                fallback: bool,
+            ) -> Result<String, RestApiError> {
+                match (id, fallback) {
+                    (EntityId::Property(_), false) => $property_endpoint {
+                        property_id: id,
+                        language_code: language,
+                    }
+                    .path(),
+                    (EntityId::Property(_), true) => $property_fallback_endpoint {
+                        property_id: id,
+                        language_code: language,
+                    }
+                    .path(),
+                    (_, false) => $item_endpoint {
+                        item_id: id,
+                        language_code: language,
+                    }
+                    .path(),
+                    (_, true) => $item_fallback_endpoint {
+                        item_id: id,
+                        language_code: language,
+                    }
+                    .path(),
+                }
+            }
+
             async fn generate_get_match_request(
                 id: &EntityId,
                 language: &str,
                 api: &RestApi,
                 rm: RevisionMatch,
-                mode: &str,
+                fallback: bool,
             ) -> Result<Request, RestApiError> {
-                let path = format!(
-                    "/entities/{group}/{id}/{mode}/{language}",
-                    group = id.group()?
-                );
+                let path = Self::rest_api_path(id, language, fallback)?;
                 let mut request = api
                     .wikibase_request_builder(&path, HashMap::new(), reqwest::Method::GET)
                     .await?
@@ -84,9 +119,7 @@ macro_rules! impl_language_string_value {
 
         impl HttpMisc for $type_name {
             fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-                let group = id.group()?;
-                let language = Language::validated(self.ls.language())?;
-                Ok(format!("/entities/{group}/{id}/{}/{language}", $path_segment))
+                Self::rest_api_path(id, self.ls.language(), false)
             }
         }
 
@@ -98,10 +131,8 @@ macro_rules! impl_language_string_value {
                 rm: RevisionMatch,
             ) -> Result<Self, RestApiError> {
                 let language = Language::validated(language)?;
-                let request = Self::generate_get_match_request(
-                    id, &language, api, rm, $fallback_segment,
-                )
-                .await?;
+                let request =
+                    Self::generate_get_match_request(id, &language, api, rm, true).await?;
                 let (j, header_info) = Self::api_execute(api, request).await?;
                 let s = j
                     .as_str()
@@ -125,7 +156,7 @@ macro_rules! impl_language_string_value {
             ) -> Result<Self, RestApiError> {
                 let language = Language::validated(language)?;
                 let request =
-                    Self::generate_get_match_request(id, &language, api, rm, $path_segment).await?;
+                    Self::generate_get_match_request(id, &language, api, rm, false).await?;
                 let (j, header_info) = Self::api_execute(api, request).await?;
                 let s = j
                     .as_str()
@@ -180,17 +211,23 @@ macro_rules! impl_language_string_value {
 }
 
 /// Generates a language string collection type (Labels or Descriptions) with all trait implementations.
+///
+/// `$item_endpoint` and `$property_endpoint` are the endpoints of the collection of an item or
+/// a property (e.g., `GetItemLabels`, `GetPropertyLabels`).
 macro_rules! impl_language_string_collection {
     (
         $type_name:ident,
-        $path_segment:literal,
+        $item_endpoint:ident,
+        $property_endpoint:ident,
         $error_field:literal,
         $patch_constructor:ident
     ) => {
         use crate::{
-            language_strings_patch::LanguageStringsPatch, prelude::LanguageStrings, EntityId,
-            FromJson, HeaderInfo, HttpGetEntity, HttpMisc, LanguageString, RestApi, RestApiError,
-            RevisionMatch,
+            endpoints::{$item_endpoint, $property_endpoint, Endpoint},
+            language_strings_patch::LanguageStringsPatch,
+            prelude::LanguageStrings,
+            EntityId, FromJson, HeaderInfo, HttpGetEntity, HttpMisc, LanguageString, RestApi,
+            RestApiError, RevisionMatch,
         };
         use derive_where::DeriveWhere;
         use serde::ser::{Serialize, SerializeMap};
@@ -234,9 +271,11 @@ macro_rules! impl_language_string_collection {
         }
 
         impl HttpMisc for $type_name {
-            fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-                let group = id.group()?;
-                Ok(format!("/entities/{group}/{id}/{}", $path_segment))
+            fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
+                match id {
+                    EntityId::Property(_) => $property_endpoint { property_id: id }.path(),
+                    _ => $item_endpoint { item_id: id }.path(),
+                }
             }
         }
 
@@ -246,8 +285,7 @@ macro_rules! impl_language_string_collection {
                 api: &RestApi,
                 rm: RevisionMatch,
             ) -> Result<Self, RestApiError> {
-                let group = id.group()?;
-                let path = format!("/entities/{group}/{id}/{}", $path_segment);
+                let path = Self::get_rest_api_path(id)?;
                 let (j, header_info) = Self::get_match_internal(api, &path, rm).await?;
                 Self::from_json_header_info(&j, header_info)
             }
@@ -310,13 +348,21 @@ macro_rules! impl_language_string_collection {
 }
 
 /// Generates a language string patch type (`LabelsPatch` or `DescriptionsPatch`) with all trait implementations.
+///
+/// `$item_endpoint` and `$property_endpoint` are the endpoints that patch the collection of an
+/// item or a property (e.g., `PatchItemLabels`, `PatchPropertyLabels`).
 macro_rules! impl_language_string_patch {
     (
         $type_name:ident,
-        $path_segment:literal,
+        $item_endpoint:ident,
+        $property_endpoint:ident,
         $error_field:literal
     ) => {
-        use crate::{patch_entry::PatchEntry, EntityId, HttpMisc, Patch, RestApiError};
+        use crate::{
+            endpoints::{$item_endpoint, $property_endpoint, Endpoint},
+            patch_entry::PatchEntry,
+            EntityId, HttpMisc, Patch, RestApiError,
+        };
         use serde::Serialize;
         use serde_json::Value;
 
@@ -361,9 +407,11 @@ macro_rules! impl_language_string_patch {
         }
 
         impl HttpMisc for $type_name {
-            fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-                let group = id.group()?;
-                Ok(format!("/entities/{group}/{id}/{}", $path_segment))
+            fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
+                match id {
+                    EntityId::Property(_) => $property_endpoint { property_id: id }.path(),
+                    _ => $item_endpoint { item_id: id }.path(),
+                }
             }
         }
     };
@@ -0,0 +1,173 @@
+use crate::{HeaderInfo, RestApiError};
+use std::{fmt::Debug, time::Duration};
+
+/// Hooks around every request that goes through `RestApi::execute`, i.e. every request built
+/// with `RestApi::wikibase_request_builder` and sent by this crate.
+///
+/// All methods have no-op defaults, so an implementation only overrides what it needs.
+/// Middlewares are added with `RestApiBuilder::with_middleware` and run in the order they
+/// were added.
+pub trait Middleware: Debug + Send + Sync {
+    /// Called once per call, before the first attempt is sent. Changes to the request (e.g. an
+    /// added correlation-id header) are kept for all retries. Returning an error aborts the
+    /// call before anything is sent; use `RestApiError::RequestRejected` to enforce a policy.
+    fn before_request(&self, _request: &mut reqwest::Request) -> Result<(), RestApiError> {
+        Ok(())
+    }
+
+    /// Called for every response received from the transport, including ones that are then
+    /// retried. Not called if no response arrived at all.
+    fn after_response(
+        &self,
+        _method: &reqwest::Method,
+        _url: &reqwest::Url,
+        _status: reqwest::StatusCode,
+        _header_info: &HeaderInfo,
+    ) {
+    }
+
+    /// Called before waiting `delay` to retry a request that failed with `status`.
+    /// `attempt` is zero-based: the first retry follows attempt 0.
+    fn on_retry(&self, _attempt: u32, _status: reqwest::StatusCode, _delay: Duration) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RestApi;
+    use std::sync::{Arc, Mutex};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Records every hook call as a line, tagged with its name.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before_request(&self, request: &mut reqwest::Request) -> Result<(), RestApiError> {
+            request
+                .headers_mut()
+                .insert("X-Correlation-Id", "abc".parse()?);
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} before", self.name));
+            Ok(())
+        }
+
+        fn after_response(
+            &self,
+            method: &reqwest::Method,
+            _url: &reqwest::Url,
+            status: reqwest::StatusCode,
+            header_info: &HeaderInfo,
+        ) {
+            self.log.lock().unwrap().push(format!(
+                "{} after {method} {} {:?}",
+                self.name,
+                status.as_u16(),
+                header_info.revision_id()
+            ));
+        }
+
+        fn on_retry(&self, attempt: u32, status: reqwest::StatusCode, _delay: Duration) {
+            self.log.lock().unwrap().push(format!(
+                "{} retry {attempt} {}",
+                self.name,
+                status.as_u16()
+            ));
+        }
+    }
+
+    #[derive(Debug)]
+    struct DenyWrites;
+
+    impl Middleware for DenyWrites {
+        fn before_request(&self, request: &mut reqwest::Request) -> Result<(), RestApiError> {
+            if request.method() == reqwest::Method::GET {
+                Ok(())
+            } else {
+                Err(RestApiError::RequestRejected("read-only".into()))
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_hooks_run_in_order() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/openapi.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/openapi.json"))
+            .and(header("X-Correlation-Id", "abc"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"42\"")
+                    .set_body_json(serde_json::json!({})),
+            )
+            .mount(&mock_server)
+            .await;
+        let log = Arc::new(Mutex::new(vec![]));
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_retry_base_delay(Duration::from_millis(1))
+            .with_middleware(Recorder {
+                name: "a",
+                log: log.clone(),
+            })
+            .with_middleware(Recorder {
+                name: "b",
+                log: log.clone(),
+            })
+            .build()
+            .unwrap();
+        api.get_openapi_json().await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "a before",
+                "b before",
+                "a after GET 503 None",
+                "b after GET 503 None",
+                "a retry 0 503",
+                "b retry 0 503",
+                "a after GET 200 Some(42)",
+                "b after GET 200 Some(42)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_before_request_can_reject() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_middleware(DenyWrites)
+            .build()
+            .unwrap();
+        let request = api
+            .wikibase_request_builder("/entities/items", Default::default(), reqwest::Method::POST)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        let result = api.execute(request).await;
+        assert!(
+            matches!(result, Err(RestApiError::RequestRejected(reason)) if reason == "read-only")
+        );
+    }
+}
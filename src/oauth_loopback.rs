@@ -0,0 +1,359 @@
+//! Completes the `OAuth2` authorization code flow for command-line tools, by receiving the
+//! redirect on a temporary HTTP listener on a loopback address.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), wikibase_rest_api::RestApiError> {
+//! use wikibase_rest_api::{oauth_loopback::LoopbackAuthorization, RestApi};
+//!
+//! let api = RestApi::builder("https://test.wikidata.org/w/rest.php")?
+//!     .with_oauth2_public_client("YOUR_CLIENT_ID")
+//!     .build()?;
+//! let authorization = LoopbackAuthorization::start(&api).await?;
+//! println!("Open {} in your browser", authorization.url());
+//! authorization.finish(&api).await?;
+//! // `api` now sends the new access token.
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{RestApi, RestApiError};
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+/// The redirect path used when the token has no redirect URI of its own.
+const DEFAULT_PATH: &str = "/callback";
+
+/// How long a connection may take to send its request before it is dropped, so an idle
+/// connection cannot hold up the redirect.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DONE_PAGE: &str =
+    "<html><body>Authorization complete; you can close this window.</body></html>";
+const DENIED_PAGE: &str = "<html><body>Authorization was not granted.</body></html>";
+const FAILED_PAGE: &str =
+    "<html><body>Authorization failed; check the application for details.</body></html>";
+
+/// An authorization code flow waiting for the user's browser to be redirected back.
+#[derive(Debug)]
+pub struct LoopbackAuthorization {
+    listener: TcpListener,
+    path: String,
+    url: String,
+}
+
+impl LoopbackAuthorization {
+    /// Starts listening for the redirect and begins the flow with PKCE and `state`
+    /// (see `BearerToken::start_authorization`).
+    ///
+    /// If the token has a redirect URI, it must point to a loopback IP literal (`127.0.0.1`
+    /// or `[::1]`) over `http`, and its address, port and path are used; this suits clients
+    /// registered with a fixed redirect URI. `localhost` is not accepted, since the browser
+    /// may resolve it to an address the listener is not bound to. Otherwise a free port is
+    /// picked and the redirect URI is set to `http://127.0.0.1:<port>/callback`.
+    /// # Errors
+    /// Returns an error if the redirect URI is not a loopback URL, if the listener cannot be
+    /// bound, or if the authorization URL cannot be built.
+    pub async fn start(api: &RestApi) -> Result<Self, RestApiError> {
+        let token = api.token();
+        let mut token = token.write().await;
+        let (listener, path) = match token.redirect_uri() {
+            Some(redirect_uri) => {
+                let (ip, port, path) = Self::parse_redirect_uri(redirect_uri)?;
+                (Self::bind(ip, port).await?, path)
+            }
+            None => {
+                let listener = Self::bind(Ipv4Addr::LOCALHOST.into(), 0).await?;
+                let port = listener
+                    .local_addr()
+                    .map_err(RestApiError::Loopback)?
+                    .port();
+                token.set_redirect_uri(format!("http://127.0.0.1:{port}{DEFAULT_PATH}"));
+                (listener, DEFAULT_PATH.to_string())
+            }
+        };
+        let url = token.start_authorization(api)?;
+        Ok(Self {
+            listener,
+            path,
+            url,
+        })
+    }
+
+    /// Returns the authorization URL the user needs to open in a browser.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the local address the listener is bound to.
+    /// # Errors
+    /// Returns an error if the address cannot be determined.
+    pub fn local_addr(&self) -> Result<std::net::SocketAddr, RestApiError> {
+        self.listener.local_addr().map_err(RestApiError::Loopback)
+    }
+
+    /// Waits for the redirect, exchanges the code for tokens, which are installed into the
+    /// shared token of `api`, and then tells the browser whether that worked. Requests to
+    /// other paths (e.g. a browser's `/favicon.ico`) are answered with 404 and otherwise
+    /// ignored, and connections that send no request within a few seconds are dropped.
+    ///
+    /// This waits for as long as it takes the user; wrap it in `tokio::time::timeout` to
+    /// give up eventually.
+    /// # Errors
+    /// Returns `RestApiError::AuthorizationDenied` if the redirect carries an `error`,
+    /// `RestApiError::OAuth2StateMismatch` if its `state` does not match, or an error if the
+    /// listener fails or the token exchange fails.
+    pub async fn finish(self, api: &RestApi) -> Result<(), RestApiError> {
+        let (mut stream, code, state) = loop {
+            let (mut stream, _) = self
+                .listener
+                .accept()
+                .await
+                .map_err(RestApiError::Loopback)?;
+            let Ok(redirect) =
+                tokio::time::timeout(READ_TIMEOUT, self.read_redirect(&mut stream)).await
+            else {
+                continue;
+            };
+            let Some(query) = redirect? else {
+                Self::respond(&mut stream, "404 Not Found", "").await?;
+                continue;
+            };
+            if let Some(error) = query.get("error") {
+                Self::respond(&mut stream, "200 OK", DENIED_PAGE).await?;
+                return Err(RestApiError::AuthorizationDenied(error.to_owned()));
+            }
+            match (query.get("code"), query.get("state")) {
+                (Some(code), Some(state)) => break (stream, code.to_owned(), state.to_owned()),
+                _ => Self::respond(&mut stream, "400 Bad Request", "").await?,
+            }
+        };
+        let result = api
+            .token()
+            .write()
+            .await
+            .complete_authorization(api, &code, &state)
+            .await;
+        let page = if result.is_ok() {
+            DONE_PAGE
+        } else {
+            FAILED_PAGE
+        };
+        Self::respond(&mut stream, "200 OK", page).await?;
+        result
+    }
+
+    /// Returns the address, port and path of a loopback redirect URI.
+    fn parse_redirect_uri(redirect_uri: &str) -> Result<(IpAddr, u16, String), RestApiError> {
+        let invalid = || {
+            RestApiError::Loopback(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not a loopback redirect URI: {redirect_uri}"),
+            ))
+        };
+        let url = Url::parse(redirect_uri).map_err(|_| invalid())?;
+        let ip = url
+            .host_str()
+            .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+            .and_then(|host| host.parse::<IpAddr>().ok())
+            .filter(IpAddr::is_loopback)
+            .ok_or_else(invalid)?;
+        if url.scheme() != "http" {
+            return Err(invalid());
+        }
+        let port = url.port().unwrap_or(80);
+        Ok((ip, port, url.path().to_string()))
+    }
+
+    async fn bind(ip: IpAddr, port: u16) -> Result<TcpListener, RestApiError> {
+        TcpListener::bind((ip, port))
+            .await
+            .map_err(RestApiError::Loopback)
+    }
+
+    /// Reads a request and returns its query parameters, or `None` if it is not a `GET` for
+    /// the redirect path.
+    async fn read_redirect(
+        &self,
+        stream: &mut TcpStream,
+    ) -> Result<Option<HashMap<String, String>>, RestApiError> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader
+            .read_line(&mut request_line)
+            .await
+            .map_err(RestApiError::Loopback)?;
+        // Drain the headers so the browser sees a clean response.
+        let mut line = String::new();
+        while reader
+            .read_line(&mut line)
+            .await
+            .map_err(RestApiError::Loopback)?
+            > 2
+        {
+            line.clear();
+        }
+        let mut parts = request_line.split_whitespace();
+        let (Some("GET"), Some(target)) = (parts.next(), parts.next()) else {
+            return Ok(None);
+        };
+        let Ok(url) = Url::parse(&format!("http://127.0.0.1{target}")) else {
+            return Ok(None);
+        };
+        if url.path() != self.path {
+            return Ok(None);
+        }
+        Ok(Some(url.query_pairs().into_owned().collect()))
+    }
+
+    async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<(), RestApiError> {
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream
+            .write_all(response.as_bytes())
+            .await
+            .map_err(RestApiError::Loopback)?;
+        stream.shutdown().await.map_err(RestApiError::Loopback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn api_and_token_endpoint() -> (RestApi, MockServer) {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .and(body_string_contains("code=the_code"))
+            .and(body_string_contains("code_verifier="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "loopback_access",
+                "refresh_token": "loopback_refresh",
+                "expires_in": 3600,
+            })))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_oauth2_public_client("cli_tool")
+            .build()
+            .unwrap();
+        (api, mock_server)
+    }
+
+    /// Plays the browser: requests each target from the listener in turn.
+    fn browser(
+        addr: std::net::SocketAddr,
+        targets: Vec<String>,
+    ) -> tokio::task::JoinHandle<Vec<(u16, String)>> {
+        tokio::spawn(async move {
+            let mut pages = vec![];
+            for target in targets {
+                let response = reqwest::get(format!("http://{addr}{target}"))
+                    .await
+                    .unwrap();
+                let status = response.status().as_u16();
+                pages.push((status, response.text().await.unwrap()));
+            }
+            pages
+        })
+    }
+
+    #[test]
+    fn test_parse_redirect_uri() {
+        assert_eq!(
+            LoopbackAuthorization::parse_redirect_uri("http://127.0.0.1:8080/cb").unwrap(),
+            (Ipv4Addr::LOCALHOST.into(), 8080, "/cb".to_string())
+        );
+        assert_eq!(
+            LoopbackAuthorization::parse_redirect_uri("http://[::1]/").unwrap(),
+            (std::net::Ipv6Addr::LOCALHOST.into(), 80, "/".to_string())
+        );
+        assert!(LoopbackAuthorization::parse_redirect_uri("http://localhost/").is_err());
+        assert!(LoopbackAuthorization::parse_redirect_uri("http://192.0.2.1/cb").is_err());
+        assert!(LoopbackAuthorization::parse_redirect_uri("https://example.org/cb").is_err());
+        assert!(LoopbackAuthorization::parse_redirect_uri("https://127.0.0.1/cb").is_err());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_loopback_authorization() {
+        let (api, _mock_server) = api_and_token_endpoint().await;
+        let authorization = LoopbackAuthorization::start(&api).await.unwrap();
+        let addr = authorization.local_addr().unwrap();
+        assert!(authorization.url().contains("code_challenge="));
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", addr.port());
+        let state = {
+            let token = api.token();
+            let token = token.read().await;
+            assert_eq!(token.redirect_uri(), Some(redirect_uri.as_str()));
+            token.state().unwrap().to_string()
+        };
+
+        let browser = browser(
+            addr,
+            vec![
+                "/favicon.ico".to_string(),
+                format!("/callback?code=the_code&state={state}"),
+            ],
+        );
+        authorization.finish(&api).await.unwrap();
+        assert_eq!(
+            browser.await.unwrap(),
+            vec![(404, String::new()), (200, DONE_PAGE.to_string())]
+        );
+        let token = api.token();
+        let token = token.read().await;
+        assert_eq!(token.get(), &Some("loopback_access".into()));
+        assert_eq!(token.refresh_token(), &Some("loopback_refresh".into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_loopback_authorization_rejected() {
+        let (api, _mock_server) = api_and_token_endpoint().await;
+
+        let forged = LoopbackAuthorization::start(&api).await.unwrap();
+        let forged_browser = browser(
+            forged.local_addr().unwrap(),
+            vec!["/callback?code=the_code&state=forged".to_string()],
+        );
+        assert!(matches!(
+            forged.finish(&api).await,
+            Err(RestApiError::OAuth2StateMismatch)
+        ));
+        assert_eq!(
+            forged_browser.await.unwrap(),
+            vec![(200, FAILED_PAGE.to_string())]
+        );
+
+        let denied = LoopbackAuthorization::start(&api).await.unwrap();
+        let denied_browser = browser(
+            denied.local_addr().unwrap(),
+            vec!["/callback?error=access_denied".to_string()],
+        );
+        match denied.finish(&api).await {
+            Err(RestApiError::AuthorizationDenied(error)) => assert_eq!(error, "access_denied"),
+            other => panic!("Wrong result: {other:?}"),
+        }
+        assert_eq!(
+            denied_browser.await.unwrap(),
+            vec![(200, DENIED_PAGE.to_string())]
+        );
+        assert!(!api.token().read().await.has_access_token());
+    }
+}
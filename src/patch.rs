@@ -1,5 +1,6 @@
 use crate::{
-    patch_entry::PatchEntry, EditMetadata, EntityId, HeaderInfo, HttpMisc, RestApi, RestApiError,
+    patch_entry::PatchEntry, trace, EditMetadata, EntityId, HeaderInfo, HttpMisc, RestApi,
+    RestApiError,
 };
 use serde_json::{json, Value};
 
@@ -41,6 +42,14 @@ pub trait PatchApply<T: FromJson>: HttpMisc + Patch {
     }
 
     /// Applies the entire patch against the API, conditional on metadata
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "patch.apply",
+            skip_all,
+            fields(entity_id = %id, revision_id = tracing::field::Empty)
+        )
+    )]
     async fn apply_match(
         &self,
         id: &EntityId,
@@ -53,6 +62,7 @@ pub trait PatchApply<T: FromJson>: HttpMisc + Patch {
             .await?;
         let response = api.execute(request).await?;
         let (j1, header_info) = self.filter_response_error(response).await?;
+        trace::record_revision_id(&header_info);
         T::from_json_header_info(&j1, header_info)
     }
 }
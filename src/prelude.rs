@@ -1,18 +1,32 @@
 // Traits
+pub use crate::endpoints::Endpoint;
 pub use crate::entity::Entity;
 pub use crate::get_put_delete::{
     HttpDelete, HttpGet, HttpGetEntity, HttpGetEntityWithFallback, HttpMisc, HttpPut,
 };
 pub use crate::language_strings::LanguageStrings;
+pub use crate::middleware::Middleware;
+pub use crate::retry::RetryPolicy;
+pub use crate::token_store::TokenStore;
+pub use crate::transport::Transport;
 
 // Structs and enums
 pub use crate::aliases::Aliases;
 pub use crate::aliases_in_language::AliasesInLanguage;
 pub use crate::aliases_patch::AliasesPatch;
+pub use crate::bearer_token::TokenEvent;
+pub use crate::bot_password::{BotPassword, CookieSession};
+pub use crate::capabilities::Capabilities;
+pub use crate::cassette::{Cassette, CassetteMode};
+pub use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+pub use crate::concurrency::AdaptiveConcurrency;
+pub use crate::conditional::Conditional;
 pub use crate::description::Description;
 pub use crate::descriptions::Descriptions;
 pub use crate::descriptions_patch::DescriptionsPatch;
+pub use crate::dry_run::PlannedWrite;
 pub use crate::edit_metadata::EditMetadata;
+pub use crate::endpoints::Operation;
 pub use crate::entity::EntityType;
 pub use crate::entity_container::{EntityContainer, EntityContainerBuilder, LoadReport};
 pub use crate::entity_id::EntityId;
@@ -26,11 +40,16 @@ pub use crate::language_string::{Language, LanguageString};
 pub use crate::property::Property;
 pub use crate::property_value::PropertyType;
 pub use crate::property_value::PropertyValue;
+pub use crate::rate_limit::RateLimit;
 pub use crate::reference::Reference;
+pub use crate::response_cache::ResponseCache;
 pub use crate::rest_api::RestApi;
 pub use crate::rest_api_builder::RestApiBuilder;
+pub use crate::retry::{ExponentialBackoff, FixedDelay, NeverRetry, RetryContext};
 pub use crate::revision_match::RevisionMatch;
+pub use crate::schema_validation::{PayloadKind, SchemaReport, SchemaViolation};
 pub use crate::search::{Search, SearchLimit, SearchResult};
+pub use crate::secret::Secret;
 pub use crate::sitelink::{SiteId, Sitelink};
 pub use crate::sitelinks::Sitelinks;
 pub use crate::sitelinks_patch::SitelinksPatch;
@@ -42,5 +61,8 @@ pub use crate::statement_value_content::{
     StatementValueContent, TimePrecision, GREGORIAN_CALENDAR, JULIAN_CALENDAR,
 };
 pub use crate::statements::Statements;
+pub use crate::stats::{EndpointGroup, LatencyHistogram, RequestStats};
+pub use crate::token_store::{FileTokenStore, MemoryTokenStore, StoredTokens};
+pub use crate::transport::ReqwestTransport;
 pub use crate::DataType;
 pub use crate::Patch;
@@ -0,0 +1,210 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A request budget: at most `requests` requests per `period`, with bursts of up to
+/// `requests` requests allowed after a quiet spell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    requests: u32,
+    period: Duration,
+}
+
+impl RateLimit {
+    /// Creates a budget of `requests` requests per `period`. A zero `requests` is treated as 1.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self {
+            requests: requests.max(1),
+            period,
+        }
+    }
+
+    /// Creates a budget of `requests` requests per second.
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Creates a budget of `requests` requests per minute, e.g. for bot edit-rate policies.
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Returns the number of requests allowed per period.
+    pub const fn requests(&self) -> u32 {
+        self.requests
+    }
+
+    /// Returns the period.
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// A token bucket. Tokens may go negative: each caller reserves its slot immediately and is
+/// told how long to wait for it, so concurrent callers queue up in order instead of polling.
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.requests),
+            last_refill: now,
+        }
+    }
+
+    /// Tokens added per second.
+    fn rate(&self) -> f64 {
+        f64::from(self.limit.requests) / self.limit.period.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Takes one token and returns how long the caller has to wait before using it.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = self.last_refill.max(now);
+        let capacity = f64::from(self.limit.requests);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate()).min(capacity);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate())
+        }
+    }
+}
+
+/// Separate read (GET) and write (everything else) budgets, shared by all clones of a
+/// `RestApi`.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    read: Option<Mutex<TokenBucket>>,
+    write: Option<Mutex<TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(read: Option<RateLimit>, write: Option<RateLimit>) -> Self {
+        let now = Instant::now();
+        Self {
+            read: read.map(|limit| Mutex::new(TokenBucket::new(limit, now))),
+            write: write.map(|limit| Mutex::new(TokenBucket::new(limit, now))),
+        }
+    }
+
+    /// Returns the configured read budget, if any.
+    pub(crate) fn read_limit(&self) -> Option<RateLimit> {
+        Self::limit(self.read.as_ref())
+    }
+
+    /// Returns the configured write budget, if any.
+    pub(crate) fn write_limit(&self) -> Option<RateLimit> {
+        Self::limit(self.write.as_ref())
+    }
+
+    fn limit(bucket: Option<&Mutex<TokenBucket>>) -> Option<RateLimit> {
+        bucket?.lock().ok().map(|bucket| bucket.limit)
+    }
+
+    /// Waits until the budget for `method` allows another request.
+    pub(crate) async fn acquire(&self, method: &reqwest::Method) {
+        let bucket = if *method == reqwest::Method::GET {
+            &self.read
+        } else {
+            &self.write
+        };
+        let Some(bucket) = bucket else {
+            return;
+        };
+        let delay = bucket
+            .lock()
+            .map_or(Duration::ZERO, |mut bucket| bucket.reserve(Instant::now()));
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RestApi;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_rate_limit_constructors() {
+        assert_eq!(RateLimit::per_second(5).period(), Duration::from_secs(1));
+        assert_eq!(RateLimit::per_minute(30).period(), Duration::from_secs(60));
+        assert_eq!(RateLimit::per_minute(0).requests(), 1);
+    }
+
+    #[test]
+    fn test_token_bucket_burst_then_wait() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::per_second(2), start);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        // Third and fourth requests queue up behind each other.
+        assert_eq!(bucket.reserve(start), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(start), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_token_bucket_refills_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::per_second(2), start);
+        bucket.reserve(start);
+        bucket.reserve(start);
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert!(bucket.reserve(later) > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_rate_limiter_without_budget_does_not_wait() {
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+        for _ in 0..100 {
+            limiter.acquire(&reqwest::Method::POST).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert!(limiter.read_limit().is_none());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_write_budget_is_shared_across_clones() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_write_rate_limit(RateLimit::new(1, Duration::from_millis(100)))
+            .build()
+            .unwrap();
+        let clone = api.clone();
+        assert_eq!(
+            clone.write_rate_limit(),
+            Some(RateLimit::new(1, Duration::from_millis(100)))
+        );
+        assert!(clone.read_rate_limit().is_none());
+
+        let url = mock_server.uri() + "/w/rest.php/wikibase/v1/entities/items";
+        let start = Instant::now();
+        // Three writes over two clones: the second and third wait for the shared budget.
+        for target in [&api, &clone, &api] {
+            let request = target.client().post(&url).build().unwrap();
+            target.execute(request).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,563 @@
+use crate::{RestApi, RestApiError};
+use reqwest::header::{
+    HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, ETAG, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Headers that mark a request as conditional. A caller who sets any of these wants to see
+/// the server's answer (e.g. a 304) verbatim, so such requests bypass the cache.
+const CONDITIONAL_HEADERS: [&str; 4] = [
+    "If-None-Match",
+    "If-Match",
+    "If-Modified-Since",
+    "If-Unmodified-Since",
+];
+
+/// Headers that identify the user. Responses may differ per user (e.g. hidden revisions), so
+/// these are part of the cache key.
+const CREDENTIAL_HEADERS: [reqwest::header::HeaderName; 2] = [AUTHORIZATION, COOKIE];
+
+/// A cached response body together with the validators needed to revalidate it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedResponse {
+    etag: String,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+    body: String,
+}
+
+impl CachedResponse {
+    fn from_parts(headers: &HeaderMap, body: &[u8]) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+        };
+        Some(Self {
+            etag: header(ETAG)?.to_string(),
+            last_modified: header(LAST_MODIFIED).map(ToString::to_string),
+            content_type: header(CONTENT_TYPE).map(ToString::to_string),
+            body: String::from_utf8(body.to_vec()).ok()?,
+        })
+    }
+
+    /// Rebuilds a `200 OK` response carrying the cached body and validators.
+    fn to_response(&self) -> Result<reqwest::Response, RestApiError> {
+        let mut builder = http::Response::builder()
+            .status(StatusCode::OK)
+            .header(ETAG, &self.etag);
+        if let Some(last_modified) = &self.last_modified {
+            builder = builder.header(LAST_MODIFIED, last_modified);
+        }
+        if let Some(content_type) = &self.content_type {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        let response = builder
+            .body(self.body.clone())
+            .map_err(|e| RestApiError::EmptyValue(e.to_string()))?;
+        Ok(reqwest::Response::from(response))
+    }
+}
+
+/// The in-memory part of the cache: a map with a use counter, and the keys ordered by last
+/// use, so the least recently used entry is evicted in logarithmic time.
+#[derive(Debug, Default)]
+struct LruStore {
+    entries: HashMap<String, (u64, CachedResponse)>,
+    by_use: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl LruStore {
+    fn get(&mut self, key: &str) -> Option<CachedResponse> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (used, entry) = self.entries.get_mut(key)?;
+        self.by_use.remove(used);
+        self.by_use.insert(tick, key.to_string());
+        *used = tick;
+        Some(entry.clone())
+    }
+
+    fn insert(&mut self, key: String, entry: CachedResponse, capacity: usize) {
+        self.tick += 1;
+        if let Some((used, _)) = self.entries.get(&key) {
+            self.by_use.remove(used);
+        } else if self.entries.len() >= capacity {
+            if let Some((_, oldest)) = self.by_use.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.by_use.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, entry));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.by_use.clear();
+    }
+}
+
+/// An ETag-aware cache for GET responses, shared by every clone of the `RestApi` it is
+/// configured on. Entries are kept per user, i.e. per `Authorization` and `Cookie` header,
+/// so contexts created with `RestApi::as_user` never see each other's responses.
+///
+/// Cached entries are revalidated with `If-None-Match`; a `304 Not Modified` is answered
+/// from the cache as if the server had sent the full body. Optionally, entries are also kept
+/// on disk, and a stale entry can be served when the server is unreachable (stale-if-error).
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    memory: Arc<Mutex<LruStore>>,
+    capacity: usize,
+    disk_store: Option<PathBuf>,
+    stale_if_error: bool,
+}
+
+impl ResponseCache {
+    /// Creates an in-memory cache holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            memory: Arc::new(Mutex::new(LruStore::default())),
+            capacity: capacity.max(1),
+            disk_store: None,
+            stale_if_error: false,
+        }
+    }
+
+    /// Also keeps cached responses as files in `directory`, so they survive process restarts.
+    /// The directory is created on first write if it does not exist.
+    ///
+    /// Response bodies are written in plaintext, including those of authenticated requests
+    /// (which may show e.g. hidden or private data), so `directory` should be readable only
+    /// by the user running the bot. Credentials themselves are only stored as a hash.
+    pub fn with_disk_store<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.disk_store = Some(directory.into());
+        self
+    }
+
+    /// Serves a cached (possibly stale) response when the server cannot be reached or keeps
+    /// answering with a server error. Default is `false`.
+    pub const fn with_stale_if_error(mut self, stale_if_error: bool) -> Self {
+        self.stale_if_error = stale_if_error;
+        self
+    }
+
+    /// Returns the number of responses held in memory.
+    pub fn len(&self) -> usize {
+        self.memory.lock().map_or(0, |store| store.entries.len())
+    }
+
+    /// Returns `true` if no responses are held in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all responses from memory. Files in the disk store are left untouched.
+    pub fn clear(&self) {
+        if let Ok(mut store) = self.memory.lock() {
+            store.clear();
+        }
+    }
+
+    /// Returns `true` if the request can be served through the cache: a GET that the caller
+    /// has not already made conditional.
+    pub(crate) fn is_cacheable(request: &reqwest::Request) -> bool {
+        *request.method() == reqwest::Method::GET
+            && !CONDITIONAL_HEADERS
+                .iter()
+                .any(|name| request.headers().contains_key(*name))
+    }
+
+    /// Executes a cacheable request via `api`, revalidating any cached entry.
+    pub(crate) async fn execute(
+        &self,
+        api: &RestApi,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let key = Self::key(&request);
+        let cached = self.lookup(&key);
+        if let Some(entry) = &cached {
+            request
+                .headers_mut()
+                .insert(IF_NONE_MATCH, HeaderValue::from_str(&entry.etag)?);
+        }
+        let result = api.execute_with_retries(request).await;
+        match (result, cached) {
+            (Ok(response), Some(entry)) if response.status() == StatusCode::NOT_MODIFIED => {
+                entry.to_response()
+            }
+            (Ok(response), _) if response.status() == StatusCode::OK => {
+                self.store(key, response).await
+            }
+            (Ok(response), Some(entry))
+                if self.stale_if_error && response.status().is_server_error() =>
+            {
+                entry.to_response()
+            }
+            (Ok(response), _) => Ok(response),
+            (Err(e), Some(entry)) if self.stale_if_error && Self::is_unreachable(&e) => {
+                entry.to_response()
+            }
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    /// Returns the key for `request`: its URL, and a fingerprint of its credentials, if any.
+    /// Credentials are hashed so they are not written to the disk store.
+    fn key(request: &reqwest::Request) -> String {
+        let mut credentials = String::new();
+        for name in &CREDENTIAL_HEADERS {
+            for value in request.headers().get_all(name) {
+                credentials.push_str(name.as_str());
+                credentials.push(':');
+                credentials.push_str(&String::from_utf8_lossy(value.as_bytes()));
+                credentials.push('\n');
+            }
+        }
+        let url = request.url().to_string();
+        if credentials.is_empty() {
+            url
+        } else {
+            format!("{url}\n{}", Self::sha256(&credentials))
+        }
+    }
+
+    /// Returns `true` for errors that mean the server could not give a proper answer.
+    fn is_unreachable(error: &RestApiError) -> bool {
+        match error {
+            RestApiError::Reqwest(_) => true,
+            RestApiError::ApiError { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        let from_memory = self.memory.lock().ok()?.get(key);
+        from_memory.or_else(|| {
+            let entry = self.read_disk(key)?;
+            self.insert_memory(key.to_string(), entry.clone());
+            Some(entry)
+        })
+    }
+
+    /// Buffers a `200 OK` response, stores it if it carries an `ETag`, and returns an
+    /// equivalent response to the caller.
+    async fn store(
+        &self,
+        key: String,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        if let Some(entry) = CachedResponse::from_parts(&headers, &body) {
+            self.write_disk(&key, &entry);
+            self.insert_memory(key, entry);
+        }
+        let mut rebuilt = http::Response::new(body);
+        *rebuilt.status_mut() = status;
+        *rebuilt.headers_mut() = headers;
+        Ok(reqwest::Response::from(rebuilt))
+    }
+
+    fn insert_memory(&self, key: String, entry: CachedResponse) {
+        if let Ok(mut store) = self.memory.lock() {
+            store.insert(key, entry, self.capacity);
+        }
+    }
+
+    fn disk_path(directory: &Path, key: &str) -> PathBuf {
+        directory.join(format!("{}.json", Self::sha256(key)))
+    }
+
+    /// SHA-256 as lowercase hex. It is stable across builds and platforms, so it names disk
+    /// store files, and it cannot be reversed to recover a credential from a key.
+    fn sha256(key: &str) -> String {
+        format!("{:x}", Sha256::digest(key.as_bytes()))
+    }
+
+    fn read_disk(&self, key: &str) -> Option<CachedResponse> {
+        let path = Self::disk_path(self.disk_store.as_ref()?, key);
+        let text = std::fs::read_to_string(path).ok()?;
+        let (stored_key, entry): (String, CachedResponse) = serde_json::from_str(&text).ok()?;
+        // Guard against (unlikely) hash collisions between keys.
+        (stored_key == key).then_some(entry)
+    }
+
+    /// Writes an entry to the disk store. The cache is an optimization, so I/O failures are
+    /// ignored rather than failing the request.
+    fn write_disk(&self, key: &str, entry: &CachedResponse) {
+        let Some(directory) = &self.disk_store else {
+            return;
+        };
+        let Ok(text) = serde_json::to_string(&(key, entry)) else {
+            return;
+        };
+        if std::fs::create_dir_all(directory).is_ok() {
+            let _ = std::fs::write(Self::disk_path(directory, key), text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entity::Entity, EntityId, Item};
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn entry(etag: &str) -> CachedResponse {
+        CachedResponse {
+            etag: etag.to_string(),
+            last_modified: None,
+            content_type: None,
+            body: "{}".to_string(),
+        }
+    }
+
+    async fn api_with_cache(mock_server: &MockServer, cache: ResponseCache) -> RestApi {
+        RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_max_retries(0)
+            .with_cache(cache)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut store = LruStore::default();
+        store.insert("a".into(), entry("1"), 2);
+        store.insert("b".into(), entry("2"), 2);
+        // Touch "a" so that "b" becomes the least recently used entry.
+        assert!(store.get("a").is_some());
+        store.insert("c".into(), entry("3"), 2);
+        assert!(store.get("a").is_some());
+        assert!(store.get("b").is_none());
+        assert!(store.get("c").is_some());
+        // Replacing an entry counts as a use and does not evict anything.
+        store.insert("a".into(), entry("4"), 2);
+        store.insert("d".into(), entry("5"), 2);
+        assert!(store.get("a").is_some_and(|a| a.etag == "4"));
+        assert!(store.get("c").is_none());
+        assert_eq!(store.entries.len(), 2);
+        assert_eq!(store.by_use.len(), 2);
+    }
+
+    #[test]
+    fn test_sha256_is_stable() {
+        assert_eq!(
+            ResponseCache::sha256(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_is_cacheable() {
+        let url: reqwest::Url = "https://example.org/".parse().unwrap();
+        let get = reqwest::Request::new(reqwest::Method::GET, url.clone());
+        assert!(ResponseCache::is_cacheable(&get));
+        let post = reqwest::Request::new(reqwest::Method::POST, url.clone());
+        assert!(!ResponseCache::is_cacheable(&post));
+        let mut conditional = reqwest::Request::new(reqwest::Method::GET, url);
+        conditional
+            .headers_mut()
+            .insert(IF_NONE_MATCH, HeaderValue::from_static("\"1\""));
+        assert!(!ResponseCache::is_cacheable(&conditional));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_revalidates_and_serves_304_from_cache() {
+        let mock_server = MockServer::start().await;
+        let item_path = "/w/rest.php/wikibase/v1/entities/items/Q42";
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .and(header("If-None-Match", "\"123\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"123\"")
+                    .set_body_json(json!({"id": "Q42", "labels": {"en": "Douglas Adams"}})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let cache = ResponseCache::new(10);
+        let api = api_with_cache(&mock_server, cache.clone()).await;
+
+        let first = Item::get(EntityId::item("Q42"), &api).await.unwrap();
+        let second = Item::get(EntityId::item("Q42"), &api).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second.header_info().revision_id(), Some(123));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_entries_are_kept_per_user() {
+        let mock_server = MockServer::start().await;
+        let item_path = "/w/rest.php/wikibase/v1/entities/items/Q42";
+        for (user, etag) in [("alice", "\"1\""), ("bob", "\"2\"")] {
+            Mock::given(method("GET"))
+                .and(path(item_path))
+                .and(header("Authorization", format!("Bearer {user}")))
+                .and(header("If-None-Match", etag))
+                .respond_with(ResponseTemplate::new(304))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(item_path))
+                .and(header("Authorization", format!("Bearer {user}")))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("ETag", etag)
+                        .set_body_json(json!({"id": "Q42", "labels": {"en": user}})),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let cache = ResponseCache::new(10);
+        let api = api_with_cache(&mock_server, cache.clone()).await;
+        let alice = api.as_user(
+            crate::bearer_token::BearerToken::default().for_user(Some("alice".into()), None),
+        );
+        let bob = api.as_user(
+            crate::bearer_token::BearerToken::default().for_user(Some("bob".into()), None),
+        );
+
+        for _ in 0..2 {
+            let alice_item = Item::get(EntityId::item("Q42"), &alice).await.unwrap();
+            assert_eq!(alice_item.labels().get_lang("en"), Some("alice"));
+            let bob_item = Item::get(EntityId::item("Q42"), &bob).await.unwrap();
+            assert_eq!(bob_item.labels().get_lang("en"), Some("bob"));
+        }
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_key_hides_credentials() {
+        let url: reqwest::Url = "https://example.org/".parse().unwrap();
+        let anonymous = reqwest::Request::new(reqwest::Method::GET, url.clone());
+        assert_eq!(ResponseCache::key(&anonymous), "https://example.org/");
+        let mut request = reqwest::Request::new(reqwest::Method::GET, url);
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        let key = ResponseCache::key(&request);
+        assert!(key.starts_with("https://example.org/\n"));
+        assert!(!key.contains("secret"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_stale_if_error() {
+        // The server answers once, then fails.
+        async fn flaky_server() -> MockServer {
+            let mock_server = MockServer::start().await;
+            let item_path = "/w/rest.php/wikibase/v1/entities/items/Q42";
+            Mock::given(method("GET"))
+                .and(path(item_path))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("ETag", "\"7\"")
+                        .set_body_json(json!({"id": "Q42"})),
+                )
+                .up_to_n_times(1)
+                .mount(&mock_server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(item_path))
+                .respond_with(ResponseTemplate::new(503))
+                .mount(&mock_server)
+                .await;
+            mock_server
+        }
+
+        let strict_server = flaky_server().await;
+        let strict = api_with_cache(&strict_server, ResponseCache::new(10)).await;
+        Item::get(EntityId::item("Q42"), &strict).await.unwrap();
+        assert!(Item::get(EntityId::item("Q42"), &strict).await.is_err());
+
+        let lenient_server = flaky_server().await;
+        let cache = ResponseCache::new(10).with_stale_if_error(true);
+        let lenient = api_with_cache(&lenient_server, cache).await;
+        Item::get(EntityId::item("Q42"), &lenient).await.unwrap();
+        let stale = Item::get(EntityId::item("Q42"), &lenient).await.unwrap();
+        assert_eq!(stale.id(), &EntityId::item("Q42"));
+        assert_eq!(stale.header_info().revision_id(), Some(7));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_disk_store_survives_new_cache() {
+        let directory =
+            std::env::temp_dir().join(format!("wikibase_rest_api_cache_{}", uuid::Uuid::new_v4()));
+        let mock_server = MockServer::start().await;
+        let item_path = "/w/rest.php/wikibase/v1/entities/items/Q1";
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .and(header("If-None-Match", "\"5\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"5\"")
+                    .set_body_json(json!({"id": "Q1"})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let first = ResponseCache::new(10).with_disk_store(&directory);
+        let first_api = api_with_cache(&mock_server, first).await;
+        Item::get(EntityId::item("Q1"), &first_api).await.unwrap();
+
+        // A new cache (e.g. after a restart) revalidates from the disk store.
+        let second = ResponseCache::new(10).with_disk_store(&directory);
+        let second_api = api_with_cache(&mock_server, second).await;
+        let item = Item::get(EntityId::item("Q1"), &second_api).await.unwrap();
+        assert_eq!(item.id(), &EntityId::item("Q1"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_caller_conditional_request_bypasses_cache() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&mock_server)
+            .await;
+        let cache = ResponseCache::new(10);
+        let api = api_with_cache(&mock_server, cache.clone()).await;
+        let mut rm = crate::RevisionMatch::default();
+        rm.set_modified_since_revisions(vec![1]);
+        let result = Item::get_match(EntityId::item("Q42"), &api, rm).await;
+        assert!(result.is_err());
+        assert!(cache.is_empty());
+    }
+}
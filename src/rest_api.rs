@@ -1,9 +1,27 @@
-use crate::{bearer_token::BearerToken, rest_api_builder::RestApiBuilder, RestApiError};
+use crate::{
+    bearer_token::BearerToken,
+    capabilities::Capabilities,
+    circuit_breaker::{CircuitController, CircuitState},
+    coalesce::{BufferedResponse, Coalescer},
+    concurrency::ConcurrencyController,
+    dry_run::{Plan, PlannedWrite},
+    endpoints::{GetItem, GetOpenApiDoc, GetProperty, GetPropertyDataTypes},
+    middleware::Middleware,
+    rate_limit::{RateLimit, RateLimiter},
+    response_cache::ResponseCache,
+    rest_api_builder::RestApiBuilder,
+    retry::{RetryContext, RetryPolicy},
+    schema_validation::SchemaValidator,
+    stats::{RequestStats, StatsRecorder},
+    trace,
+    transport::Transport,
+    EntityId, HeaderInfo, RestApiError,
+};
 use reqwest::header::HeaderMap;
 use std::{
     collections::HashMap,
     sync::Arc,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::sync::RwLock;
 
@@ -16,6 +34,7 @@ const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
 #[derive(Debug, Clone)]
 pub struct RestApi {
     client: reqwest::Client,
+    transport: Arc<dyn Transport>,
     user_agent: String,
     api_url: String,
     api_version: u8,
@@ -23,6 +42,17 @@ pub struct RestApi {
     max_retries: u32,
     retry_base_delay: Duration,
     max_retry_after: Duration,
+    retry_policy: Arc<dyn RetryPolicy>,
+    cache: Option<ResponseCache>,
+    rate_limiter: Arc<RateLimiter>,
+    concurrency: Option<Arc<ConcurrencyController>>,
+    circuit_breaker: Option<Arc<CircuitController>>,
+    coalescer: Option<Arc<Coalescer>>,
+    capabilities: Option<Arc<Capabilities>>,
+    schema_validator: Option<Arc<SchemaValidator>>,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+    pub(crate) stats: Arc<StatsRecorder>,
+    plan: Arc<Plan>,
 }
 
 impl RestApi {
@@ -67,7 +97,13 @@ impl RestApi {
                 );
             }
         }
-        let wikibase_path = format!("{}{}", self.wikibase_root(), path.into());
+        let path = path.into();
+        if let Some(capabilities) = &self.capabilities {
+            if !capabilities.supports(&method, &format!("/v{}{path}", self.api_version)) {
+                return Err(RestApiError::NotImplementedInRestApi { method, path });
+            }
+        }
+        let wikibase_path = format!("{}{path}", self.wikibase_root());
         self.request_builder(&wikibase_path, headers, params, method)
     }
 
@@ -76,67 +112,446 @@ impl RestApi {
         RestApi::builder("https://www.wikidata.org/w/rest.php")?.build()
     }
 
-    /// Executes a `reqwest::Request` with automatic retry on 429 and 5xx errors.
-    /// Respects `Retry-After` headers when present.
+    /// Returns the response cache, if one is configured
+    pub const fn cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    /// Returns the budget for read (GET) requests, if one is configured
+    pub fn read_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limiter.read_limit()
+    }
+
+    /// Returns the budget for write (non-GET) requests, if one is configured
+    pub fn write_rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limiter.write_limit()
+    }
+
+    /// Returns the current number of allowed in-flight requests, if adaptive concurrency is
+    /// configured
+    pub fn concurrency_limit(&self) -> Option<usize> {
+        self.concurrency.as_ref().map(|c| c.limit())
+    }
+
+    /// Returns the state of the circuit breaker, if one is configured
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker.as_ref().map(|c| c.state())
+    }
+
+    /// Returns a snapshot of the request counters since this `RestApi` was built. The counters
+    /// are shared by all clones.
+    pub fn stats(&self) -> RequestStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns `true` if write requests are captured instead of sent
+    pub fn is_dry_run(&self) -> bool {
+        self.plan.enabled()
+    }
+
+    /// Returns the writes captured by dry-run mode so far, oldest first
+    pub fn planned_writes(&self) -> Vec<PlannedWrite> {
+        self.plan.writes()
+    }
+
+    /// Returns the writes captured by dry-run mode so far, and clears the list
+    pub fn take_planned_writes(&self) -> Vec<PlannedWrite> {
+        self.plan.take_writes()
+    }
+
+    /// Executes a `reqwest::Request` through the transport, with automatic retry on 429 and
+    /// 5xx errors. Respects `Retry-After` headers when present. If a `ResponseCache` is
+    /// configured, GET requests are revalidated against it. Configured middlewares run
+    /// around the request.
     /// # Errors
-    /// Returns an error if all retry attempts fail
+    /// Returns an error if all retry attempts fail, or if a middleware rejects the request.
+    /// In dry-run mode, write requests are not sent and fail with `RestApiError::DryRun`.
+    /// With schema validation, a response body that does not match the `OpenAPI` schema
+    /// fails with `RestApiError::SchemaViolations`.
+    /// If the server rejects an expired access token with 401, the token is renewed once and
+    /// the request is sent again.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "request",
+            skip_all,
+            fields(
+                method = %request.method(),
+                path = request.url().path(),
+                status = tracing::field::Empty,
+                attempt = tracing::field::Empty,
+                retry_delay_ms = tracing::field::Empty,
+            )
+        )
+    )]
     pub async fn execute(
         &self,
         request: reqwest::Request,
     ) -> Result<reqwest::Response, RestApiError> {
-        self.ensure_token_fresh(request.method()).await?;
+        let mut request = self.plan.capture(request)?;
+        self.ensure_token_fresh(&mut request).await?;
+        for middleware in self.middlewares.iter() {
+            middleware.before_request(&mut request)?;
+        }
+        let Some(validator) = &self.schema_validator else {
+            return self.dispatch_authorized(request).await;
+        };
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let response = self.dispatch_authorized(request).await?;
+        validator.check_response(&method, &url, response).await
+    }
+
+    /// Dispatches the request. If the access token can be renewed and the server rejects it
+    /// as expired, renews it and replays the request once, with the new token.
+    async fn dispatch_authorized(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let replay = if self.token.read().await.can_renew() {
+            request.try_clone()
+        } else {
+            None
+        };
+        let response = self.coalesce_or_dispatch(request).await?;
+        let Some(mut replay) = replay else {
+            return Ok(response);
+        };
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let (response, error_code) = Self::read_error_code(response).await?;
+        if !BearerToken::is_expired_token_rejection(
+            response.status(),
+            response.headers(),
+            error_code.as_deref(),
+        ) {
+            return Ok(response);
+        }
+        let mut token = self.token.write().await;
+        // A concurrent request that was rejected too may have renewed the token already.
+        let rejected = replay.headers().get(reqwest::header::AUTHORIZATION);
+        let current = self.headers_from_token(&token).await?;
+        let headers = if current.get(reqwest::header::AUTHORIZATION) == rejected {
+            token.renew_rejected_access_token(self).await?;
+            self.headers_from_token(&token).await?
+        } else {
+            current
+        };
+        drop(token);
+        if let Some(authorization) = headers.get(reqwest::header::AUTHORIZATION) {
+            replay
+                .headers_mut()
+                .insert(reqwest::header::AUTHORIZATION, authorization.clone());
+        }
+        self.coalesce_or_dispatch(replay).await
+    }
+
+    /// Shares the request with identical in-flight GETs, if coalescing is enabled.
+    async fn coalesce_or_dispatch(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let Some((coalescer, key)) = self
+            .coalescer
+            .as_ref()
+            .and_then(|coalescer| Some((coalescer, Coalescer::key(&request)?)))
+        else {
+            return self.dispatch(request).await;
+        };
+        coalescer.execute(key, self.dispatch(request)).await
+    }
+
+    /// Checks the JSON body of `request` against the `OpenAPI` schema, if schema validation
+    /// is enabled.
+    /// # Errors
+    /// Returns `RestApiError::SchemaViolations` if the body does not match the schema.
+    pub(crate) fn check_request_schema(
+        &self,
+        request: &reqwest::Request,
+    ) -> Result<(), RestApiError> {
+        self.schema_validator
+            .as_ref()
+            .map_or(Ok(()), |validator| validator.check_request(request))
+    }
 
-        for attempt in 0..=self.max_retries {
+    /// Sends a request through the cache, if it is cacheable, or with retries otherwise.
+    async fn dispatch(&self, request: reqwest::Request) -> Result<reqwest::Response, RestApiError> {
+        match &self.cache {
+            Some(cache) if ResponseCache::is_cacheable(&request) => {
+                cache.execute(self, request).await
+            }
+            _ => self.execute_with_retries(request).await,
+        }
+    }
+
+    /// Sends a request through the transport, retrying errors as the `RetryPolicy` decides.
+    ///
+    /// A 429 means the request was not processed, so it is safe to repeat. After a 5xx, a write
+    /// may have been committed anyway, so the built-in policies only retry requests that are
+    /// safe to repeat; see `is_safe_to_retry`. If the policy opts into
+    /// `RetryPolicy::reads_before_write`, a PUT, PATCH or DELETE without `If-Match` is made
+    /// safe by reading the revision of the entity before the write, and sending retries with
+    /// `If-Match` on it. A 429 or 5xx that is not retried becomes an error; other responses
+    /// are returned as they are.
+    pub(crate) async fn execute_with_retries(
+        &self,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let needs_base_revision =
+            Self::needs_base_revision(&request) && self.reads_before_write(request.method());
+        let mut base_revision = if needs_base_revision {
+            self.revision_before_write(&request).await
+        } else {
+            None
+        };
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
             // Clone for a possible retry. If the body isn't cloneable (e.g. a stream),
             // we can only send it once — execute the original and return its result.
             let req = match request.try_clone() {
                 Some(req) => req,
-                None => return Ok(self.client.execute(request).await?),
+                None => return self.send(request).await,
             };
 
-            let response = self.client.execute(req).await?;
+            let response = self.send(req).await?;
             let status = response.status();
-            let retryable =
-                status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            trace::record_response(status, attempt);
+            if !status.is_client_error() && !status.is_server_error() {
+                return Ok(response);
+            }
 
-            if retryable && attempt < self.max_retries {
-                let delay = self.retry_delay(&response, attempt);
+            let retry_after = self.capped_retry_after(&response);
+            let (response, error_code) = Self::read_error_code(response).await?;
+            let context = RetryContext::new(request.method(), status)
+                .with_error_code(error_code.as_deref())
+                .with_attempt(attempt)
+                .with_elapsed(started.elapsed())
+                .with_retry_after(retry_after)
+                .with_safe_to_repeat(
+                    status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || Self::is_safe_to_retry(&request)
+                        || base_revision.is_some(),
+                );
+            if let Some(delay) = self.retry_policy.retry_delay(&context) {
+                if let Some(revision) = base_revision.take() {
+                    request.headers_mut().insert(
+                        reqwest::header::IF_MATCH,
+                        format!("\"{revision}\"").parse()?,
+                    );
+                }
+                trace::record_retry(status, attempt, delay);
+                self.stats.record_retry(delay);
+                for middleware in self.middlewares.iter() {
+                    middleware.on_retry(attempt, status, delay);
+                }
                 tokio::time::sleep(delay).await;
+                attempt += 1;
                 continue;
             }
-            if retryable {
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
                 return Err(RestApiError::from_response(response).await);
             }
             return Ok(response);
         }
+    }
 
-        // The loop always returns; this satisfies the type checker only.
-        Err(RestApiError::EmptyValue(
-            "all retry attempts exhausted".into(),
-        ))
+    /// Buffers the body of an error response to read the `code` of its error payload, and
+    /// returns an equivalent response along with it.
+    async fn read_error_code(
+        response: reqwest::Response,
+    ) -> Result<(reqwest::Response, Option<String>), RestApiError> {
+        let buffered = BufferedResponse::read(response).await?;
+        let error_code = serde_json::from_slice::<serde_json::Value>(buffered.body())
+            .ok()
+            .and_then(|j| j["code"].as_str().map(str::to_string));
+        Ok((buffered.to_response(), error_code))
     }
 
-    /// Renews the bearer token if the request requires it. Uses a read lock for the common
-    /// case (GETs, or a token that is still fresh) and only escalates to a write lock — with a
-    /// re-check — when a renewal is actually due, so concurrent requests don't serialize.
-    async fn ensure_token_fresh(&self, method: &reqwest::Method) -> Result<(), RestApiError> {
-        if !self.token.read().await.needs_renewal(method) {
-            return Ok(());
+    /// Whether `request` may be sent again after a 5xx. Reads always may. A POST never is, as
+    /// it would create a second statement or entity if the first attempt was committed; use
+    /// `Statements::post`, which checks whether the edit landed before posting again. PUT, PATCH
+    /// and DELETE are only retried when guarded by `If-Match` (see
+    /// `EditMetadata::set_base_revision`, or the revision `execute_with_retries` reads before
+    /// the write if `RetryPolicy::reads_before_write`), so a retry of a committed write fails
+    /// with 412 instead of being applied twice.
+    pub(crate) fn is_safe_to_retry(request: &reqwest::Request) -> bool {
+        match *request.method() {
+            reqwest::Method::POST => false,
+            reqwest::Method::PUT | reqwest::Method::PATCH | reqwest::Method::DELETE => {
+                request.headers().contains_key(reqwest::header::IF_MATCH)
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns `true` for a PUT, PATCH or DELETE that is not guarded by `If-Match` yet.
+    fn needs_base_revision(request: &reqwest::Request) -> bool {
+        matches!(
+            *request.method(),
+            reqwest::Method::PUT | reqwest::Method::PATCH | reqwest::Method::DELETE
+        ) && !request.headers().contains_key(reqwest::header::IF_MATCH)
+    }
+
+    /// Returns the entity a write to `url` modifies, e.g. Q42 for
+    /// `.../entities/items/Q42/labels/en` or `.../statements/Q42$...`.
+    fn written_entity(&self, url: &reqwest::Url) -> Option<EntityId> {
+        let root = self.wikibase_root();
+        let path = url.path();
+        let path = &path[path.find(&root)? + root.len()..];
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match segments.as_slice() {
+            ["entities", _, id, ..] => EntityId::new(*id).ok(),
+            // A statement ID starts with its entity ID, followed by a (maybe encoded) `$`.
+            ["statements", statement_id, ..] => {
+                EntityId::new(statement_id.split(['$', '%']).next()?).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads the current revision of the entity `request` writes to, with the credentials of
+    /// the request. Returns `None` if that fails; the write is then not retried after a 5xx.
+    async fn revision_before_write(&self, request: &reqwest::Request) -> Option<u64> {
+        let id = self.written_entity(request.url())?;
+        let fields = Some("type");
+        let builder = match id {
+            EntityId::Property(_) => {
+                self.endpoint_request_builder(&GetProperty {
+                    property_id: &id,
+                    fields,
+                })
+                .await
+            }
+            _ => {
+                self.endpoint_request_builder(&GetItem {
+                    item_id: &id,
+                    fields,
+                })
+                .await
+            }
+        };
+        let mut read = builder.ok()?.build().ok()?;
+        for name in [reqwest::header::AUTHORIZATION, reqwest::header::COOKIE] {
+            match request.headers().get(&name) {
+                Some(value) => read.headers_mut().insert(name, value.clone()),
+                None => read.headers_mut().remove(name),
+            };
+        }
+        let response = self.send(read).await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        HeaderInfo::from_header(response.headers()).revision_id()
+    }
+
+    /// Sends a single request through the transport, once the rate limit allows it, and
+    /// reports the response to the middlewares.
+    async fn send(&self, request: reqwest::Request) -> Result<reqwest::Response, RestApiError> {
+        if self.middlewares.is_empty() {
+            return self.send_limited(request).await;
+        }
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let response = self.send_limited(request).await?;
+        let header_info = HeaderInfo::from_header(response.headers());
+        for middleware in self.middlewares.iter() {
+            middleware.after_response(&method, &url, response.status(), &header_info);
+        }
+        Ok(response)
+    }
+
+    /// Sends a single request through the transport, once the circuit breaker and the rate
+    /// limit allow it.
+    async fn send_limited(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response, RestApiError> {
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.fail_fast()?;
+        }
+        self.rate_limiter.acquire(request.method()).await;
+        let Some(concurrency) = &self.concurrency else {
+            return self.transmit(request).await;
+        };
+        let permit = concurrency.acquire().await;
+        let result = self.transmit(request).await;
+        match &result {
+            Ok(response) => {
+                permit.finish(Some(response.status()), self.capped_retry_after(response));
+            }
+            // Never sent, so it says nothing about the server.
+            Err(RestApiError::CircuitOpen(_)) => drop(permit),
+            Err(_) => permit.finish(None, None),
+        }
+        result
+    }
+
+    /// Sends a request through the transport, if the circuit breaker admits it, and records the
+    /// exchange in the statistics.
+    async fn transmit(&self, request: reqwest::Request) -> Result<reqwest::Response, RestApiError> {
+        // Admitted only now, after waiting for the rate limit, so a probe is sent right away.
+        let admission = self
+            .circuit_breaker
+            .as_deref()
+            .map(CircuitController::check)
+            .transpose()?;
+        let method = request.method().clone();
+        let path = request.url().path().to_owned();
+        let bytes_sent = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map_or(0, |body| body.len() as u64);
+        let started = Instant::now();
+        let result = self.transport.execute(request).await;
+        if let Some(admission) = admission {
+            admission.record(CircuitController::is_failure(&result));
+        }
+        self.stats.record_exchange(
+            &method,
+            &path,
+            bytes_sent,
+            result.as_ref().ok(),
+            started.elapsed(),
+        );
+        result
+    }
+
+    /// Renews the bearer token if the request requires it, and logs in with the bot password
+    /// if that has not happened yet. Uses a read lock for the common case (GETs, or a token
+    /// that is still fresh) and only escalates to a write lock — with a re-check — when a
+    /// renewal or login is actually due, so concurrent requests don't serialize. The request
+    /// then gets the new credentials.
+    async fn ensure_token_fresh(&self, request: &mut reqwest::Request) -> Result<(), RestApiError> {
+        let method = request.method().clone();
+        {
+            let token = self.token.read().await;
+            token.check_login_rejection()?;
+            if !token.needs_renewal(&method) && !token.needs_login() {
+                return Ok(());
+            }
         }
         let mut token = self.token.write().await;
-        if token.needs_renewal(method) {
+        token.check_login_rejection()?;
+        if token.needs_login() {
+            token.login(self).await?;
+        }
+        if token.needs_renewal(&method) {
             token.renew_access_token(self).await?;
         }
+        let headers = self.headers_from_token(&token).await?;
+        for name in [reqwest::header::AUTHORIZATION, reqwest::header::COOKIE] {
+            if let Some(value) = headers.get(&name) {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
         Ok(())
     }
 
-    /// Calculates the delay before retrying. Honors `Retry-After` (both delta-seconds and
-    /// HTTP-date forms), capped at `max_retry_after`; otherwise uses jittered exponential backoff.
-    fn retry_delay(&self, response: &reqwest::Response, attempt: u32) -> Duration {
-        if let Some(retry_after) = Self::retry_after(response) {
-            return retry_after.min(self.max_retry_after);
-        }
-        self.backoff_with_jitter(attempt)
+    /// Returns the delay the server asked for in `Retry-After`, capped at `max_retry_after`.
+    pub(crate) fn capped_retry_after(&self, response: &reqwest::Response) -> Option<Duration> {
+        Self::retry_after(response).map(|delay| delay.min(self.max_retry_after))
     }
 
     /// Parses a `Retry-After` header, supporting both delta-seconds and HTTP-date forms.
@@ -149,27 +564,6 @@ impl RestApi {
         when.duration_since(SystemTime::now()).ok()
     }
 
-    /// Exponential backoff with ±25% jitter, capped at `max_retry_after`.
-    /// Jitter avoids synchronized retry storms when many clients back off together.
-    fn backoff_with_jitter(&self, attempt: u32) -> Duration {
-        let multiplier = 2_u32.saturating_pow(attempt.min(16));
-        let base = self
-            .retry_base_delay
-            .saturating_mul(multiplier)
-            .min(self.max_retry_after);
-        let factor = 0.75 + Self::jitter_fraction() * 0.5; // [0.75, 1.25)
-        base.mul_f64(factor)
-    }
-
-    /// A pseudo-random fraction in [0, 1) derived from the current time. Good enough for
-    /// jitter; deliberately dependency-free (no `rand`).
-    fn jitter_fraction() -> f64 {
-        let nanos = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map_or(0, |d| d.subsec_nanos());
-        f64::from(nanos % 1000) / 1000.0
-    }
-
     /// Executes a request and returns the parsed JSON body. Any non-success status
     /// is converted into a `RestApiError::ApiError` carrying the server error payload.
     async fn execute_json(
@@ -186,12 +580,30 @@ impl RestApi {
     /// Returns the `OpenAPI` JSON for the Wikibase REST API
     pub async fn get_openapi_json(&self) -> Result<serde_json::Value, RestApiError> {
         let request = self
-            .wikibase_request_builder("/openapi.json", HashMap::new(), reqwest::Method::GET)
+            .endpoint_request_builder(&GetOpenApiDoc)
             .await?
             .build()?;
         self.execute_json(request).await
     }
 
+    /// Fetches the `OpenAPI` document and parses it into `Capabilities`.
+    /// # Errors
+    /// Returns an error if the request fails or the document has no paths.
+    pub async fn probe_capabilities(&self) -> Result<Capabilities, RestApiError> {
+        Capabilities::from_openapi_json(&self.get_openapi_json().await?)
+    }
+
+    /// Returns the capabilities of the API, if they were probed or set on the builder
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_deref()
+    }
+
+    /// Sets the capabilities, so unsupported operations fail up front.
+    pub(crate) fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(Arc::new(capabilities));
+        self
+    }
+
     /// Returns the map of property data types to value types.
     /// Keys are data-type strings (e.g. `"wikibase-item"`); values are value-type strings
     /// (e.g. `"wikibase-entityid"`).
@@ -201,7 +613,7 @@ impl RestApi {
         &self,
     ) -> Result<std::collections::HashMap<String, String>, RestApiError> {
         let request = self
-            .wikibase_request_builder("/property-data-types", HashMap::new(), reqwest::Method::GET)
+            .endpoint_request_builder(&GetPropertyDataTypes)
             .await?
             .build()?;
         let map = serde_json::from_value(self.execute_json(request).await?)?;
@@ -213,16 +625,22 @@ impl RestApi {
         &self.api_url
     }
 
-    /// Returns the `reqwest::Client`
+    /// Returns the `reqwest::Client` used to build requests
     pub const fn client(&self) -> &reqwest::Client {
         &self.client
     }
 
+    /// Returns the transport that sends requests
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
     /// Creates a new `RestApi` instance.
     /// Only available internally, use `RestApi::builder()` instead.
     #[allow(clippy::too_many_arguments)]
     pub(crate) const fn new(
         client: reqwest::Client,
+        transport: Arc<dyn Transport>,
         user_agent: String,
         api_url: String,
         api_version: u8,
@@ -230,9 +648,21 @@ impl RestApi {
         max_retries: u32,
         retry_base_delay: Duration,
         max_retry_after: Duration,
+        retry_policy: Arc<dyn RetryPolicy>,
+        cache: Option<ResponseCache>,
+        rate_limiter: Arc<RateLimiter>,
+        concurrency: Option<Arc<ConcurrencyController>>,
+        circuit_breaker: Option<Arc<CircuitController>>,
+        coalescer: Option<Arc<Coalescer>>,
+        capabilities: Option<Arc<Capabilities>>,
+        schema_validator: Option<Arc<SchemaValidator>>,
+        middlewares: Arc<[Arc<dyn Middleware>]>,
+        stats: Arc<StatsRecorder>,
+        plan: Arc<Plan>,
     ) -> Self {
         Self {
             client,
+            transport,
             user_agent,
             api_url,
             api_version,
@@ -240,10 +670,22 @@ impl RestApi {
             max_retries,
             retry_base_delay,
             max_retry_after,
+            retry_policy,
+            cache,
+            rate_limiter,
+            concurrency,
+            circuit_breaker,
+            coalescer,
+            capabilities,
+            schema_validator,
+            middlewares,
+            stats,
+            plan,
         }
     }
 
-    /// Returns a `HeaderMap` with the user agent and `OAuth2` bearer token (if present).
+    /// Returns a `HeaderMap` with the user agent, and the `OAuth2` bearer token and session
+    /// cookies (if present).
     /// Only available internally.
     pub(crate) async fn headers_from_token(
         &self,
@@ -252,10 +694,17 @@ impl RestApi {
         let mut headers = HeaderMap::new();
         headers.insert(reqwest::header::USER_AGENT, self.user_agent.parse()?);
         if let Some(access_token) = &token.get() {
-            headers.insert(
-                reqwest::header::AUTHORIZATION,
-                format!("Bearer {access_token}").parse()?,
-            );
+            let mut authorization: reqwest::header::HeaderValue =
+                format!("Bearer {}", access_token.expose()).parse()?;
+            authorization.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, authorization);
+        }
+        let cookies = token.session().and_then(|session| {
+            let url = reqwest::Url::parse(&self.api_url).ok()?;
+            session.cookie_header(&url)
+        });
+        if let Some(cookies) = cookies {
+            headers.insert(reqwest::header::COOKIE, cookies);
         }
         Ok(headers)
     }
@@ -264,12 +713,54 @@ impl RestApi {
         self.token.clone()
     }
 
-    /// Returns the maximum number of retries on 429/5xx errors.
+    /// Logs in (again) with the bot password set with `RestApiBuilder::with_bot_password`,
+    /// e.g. after the session has expired. The first login happens by itself; if the wiki
+    /// rejects it, requests fail with `RestApiError::LoginFailed` until this is called.
+    /// # Errors
+    /// Returns `RestApiError::BotPasswordRequired` if no bot password is set,
+    /// `RestApiError::LoginFailed` if the wiki rejects it, or an error if a request fails.
+    pub async fn login(&self) -> Result<(), RestApiError> {
+        self.token.write().await.login(self).await
+    }
+
+    /// Returns a `RestApi` that acts as another user, e.g. for a tool server editing on behalf
+    /// of its logged-in users. It is as cheap as a clone: the HTTP client, transport, retry
+    /// policy, rate limits, cache, circuit breaker and statistics are shared with `self`; only
+    /// the bearer token differs. See `BearerToken::for_user` to build the token.
+    pub fn as_user(&self, token: BearerToken) -> Self {
+        self.as_user_shared(Arc::new(RwLock::new(token)))
+    }
+
+    /// Like `as_user`, but with a token shared with the caller, who sees it renewed. Keep
+    /// one per user (e.g. in the user's session) so every request of that user renews it
+    /// only once.
+    pub fn as_user_shared(&self, token: Arc<RwLock<BearerToken>>) -> Self {
+        Self {
+            token,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the maximum number of retries on 429/5xx errors of the default retry policy.
     pub const fn max_retries(&self) -> u32 {
         self.max_retries
     }
 
-    /// Returns the base delay for exponential backoff retries.
+    /// Returns the `RetryPolicy`.
+    pub fn retry_policy(&self) -> &dyn RetryPolicy {
+        self.retry_policy.as_ref()
+    }
+
+    /// Returns `true` if the `RetryPolicy` opted into reading the state before a write (see
+    /// `RetryPolicy::reads_before_write`), and would retry a write with this method after a
+    /// 5xx, were it safe to repeat.
+    pub(crate) fn reads_before_write(&self, method: &reqwest::Method) -> bool {
+        let context = RetryContext::new(method, reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+            .with_safe_to_repeat(true);
+        self.retry_policy.reads_before_write() && self.retry_policy.retry_delay(&context).is_some()
+    }
+
+    /// Returns the base delay for exponential backoff retries of the default retry policy.
     pub const fn retry_base_delay(&self) -> Duration {
         self.retry_base_delay
     }
@@ -305,7 +796,8 @@ impl RestApi {
         })
     }
 
-    /// Returns a `HeaderMap` with the user agent and `OAuth2` bearer token (if present)
+    /// Returns a `HeaderMap` with the user agent, and the `OAuth2` bearer token and session
+    /// cookies (if present)
     async fn headers(&self) -> Result<HeaderMap, RestApiError> {
         let token = self.token.read().await;
         self.headers_from_token(&token).await
@@ -327,13 +819,14 @@ impl RestApi {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use wiremock::matchers::{method, path};
+    use crate::{entity::Entity, HttpPut, MemoryTokenStore, Secret, StoredTokens};
+    use wiremock::matchers::{header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
     #[cfg_attr(miri, ignore)]
     async fn test_get_openapi_json() {
-        let expected_json = std::fs::read_to_string("test_data/openapi.json").unwrap();
+        let expected_json = std::fs::read_to_string("spec/openapi.json").unwrap();
         let expected_json: serde_json::Value = serde_json::from_str(&expected_json).unwrap();
         let mock_path = "/w/rest.php/wikibase/v1/openapi.json";
         let mock_server = MockServer::start().await;
@@ -492,27 +985,77 @@ mod tests {
             .unwrap();
         // Server asks for a day; we clamp to the configured maximum.
         let response = response_with_retry_after("86400");
-        assert_eq!(api.retry_delay(&response, 0), Duration::from_secs(10));
+        assert_eq!(
+            api.capped_retry_after(&response),
+            Some(Duration::from_secs(10))
+        );
     }
 
-    #[test]
+    #[tokio::test]
     #[cfg_attr(miri, ignore)]
-    fn test_backoff_with_jitter_bounds() {
-        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+    async fn test_retry_policy_sees_error_code() {
+        #[derive(Debug)]
+        struct RetryEditConflicts;
+
+        impl RetryPolicy for RetryEditConflicts {
+            fn retry_delay(&self, context: &RetryContext) -> Option<Duration> {
+                (context.status() == reqwest::StatusCode::PRECONDITION_FAILED
+                    && context.error_code() == Some("edit-conflict")
+                    && context.attempt() == 0)
+                    .then_some(Duration::from_millis(1))
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        let mock_path = "/w/rest.php/wikibase/v1/openapi.json";
+        Mock::given(method("GET"))
+            .and(path(mock_path))
+            .respond_with(
+                ResponseTemplate::new(412)
+                    .set_body_json(serde_json::json!({"code": "edit-conflict", "message": ""})),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
             .unwrap()
-            .with_retry_base_delay(Duration::from_secs(1))
+            .with_retry_policy(RetryEditConflicts)
             .build()
             .unwrap();
-        // Attempt 2 => base 4s, jittered into [3s, 5s).
-        let delay = api.backoff_with_jitter(2);
-        assert!(delay >= Duration::from_secs(3) && delay < Duration::from_secs(5));
+
+        // Retried once, then the 412 is returned with its payload intact.
+        let result = api.get_openapi_json().await;
+        match result {
+            Err(RestApiError::ApiError {
+                status, payload, ..
+            }) => {
+                assert_eq!(status, 412);
+                assert_eq!(payload.code(), "edit-conflict");
+            }
+            other => panic!("Wrong result: {other:?}"),
+        }
+        mock_server.verify().await;
     }
 
-    #[test]
-    #[cfg_attr(miri, ignore)] // jitter_fraction() uses the realtime clock, unavailable under miri
-    fn test_jitter_fraction_range() {
-        let f = RestApi::jitter_fraction();
-        assert!((0.0..1.0).contains(&f));
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_never_retry() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_retry_policy(crate::NeverRetry)
+            .build()
+            .unwrap();
+        assert!(api
+            .get_openapi_json()
+            .await
+            .is_err_and(|e| e.is_server_error()));
+        mock_server.verify().await;
     }
 
     #[test]
@@ -595,7 +1138,7 @@ mod tests {
         {
             let mut token = api.token.write().await;
             token.set_oauth2_info("id", "secret");
-            token.set_tokens(None, Some("refresh".to_string()));
+            token.set_tokens(None, Some("refresh".into()));
         }
 
         let request = api
@@ -606,12 +1149,279 @@ mod tests {
             .unwrap();
         let response = api.execute(request).await.unwrap();
         assert!(response.status().is_success());
+        assert_eq!(api.token.read().await.get(), &Some("renewed_access".into()));
+    }
+
+    /// Mounts a token endpoint expecting `renewals` calls, and returns an API whose stored
+    /// access token is fresh as far as the client knows.
+    async fn api_with_expiring_token(mock_server: &MockServer, renewals: u64) -> RestApi {
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/oauth2/access_token"))
+            .and(wiremock::matchers::body_string_contains(
+                "refresh_token=old_refresh",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "new_access",
+                "refresh_token": "new_refresh",
+                "expires_in": 3600,
+            })))
+            .expect(renewals)
+            .mount(mock_server)
+            .await;
+        RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_oauth2_info("id", "secret")
+            .with_token_store(MemoryTokenStore::with_tokens(StoredTokens::new(
+                Some("old_access".into()),
+                Some("old_refresh".into()),
+                Some(Duration::from_secs(3600)),
+            )))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_expired_token_renewed_and_replayed() {
+        let mock_server = MockServer::start().await;
+        let item_path = "/w/rest.php/wikibase/v1/entities/items/Q42";
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .and(header("Authorization", "Bearer old_access"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .insert_header("WWW-Authenticate", "Bearer error=\"invalid_token\"")
+                    .set_body_json(serde_json::json!({
+                        "code": "mwoauth-invalid-authorization",
+                        "message": "The access token has expired",
+                    })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(item_path))
+            .and(header("Authorization", "Bearer new_access"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"id": "Q42"})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = api_with_expiring_token(&mock_server, 1).await;
+
+        let item = crate::Item::get(crate::EntityId::item("Q42"), &api)
+            .await
+            .unwrap();
+        assert_eq!(item.id(), &crate::EntityId::item("Q42"));
+        let token = api.token.read().await;
+        assert_eq!(token.get(), &Some("new_access".into()));
+        // The rotated tokens were saved to the store.
+        let stored = token.token_store().unwrap().load().unwrap().unwrap();
+        assert_eq!(
+            stored.access_token().map(Secret::expose),
+            Some("new_access")
+        );
         assert_eq!(
-            api.token.read().await.get(),
-            &Some("renewed_access".to_string())
+            stored.refresh_token().map(Secret::expose),
+            Some("new_refresh")
         );
     }
 
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_other_401_not_replayed() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "code": "permission-denied",
+                "message": "nope",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = api_with_expiring_token(&mock_server, 0).await;
+
+        let error = crate::Item::get(crate::EntityId::item("Q42"), &api)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, RestApiError::ApiError { status, .. } if status == 401));
+        assert_eq!(api.token.read().await.get(), &Some("old_access".into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_as_user() {
+        let mock_server = MockServer::start().await;
+        let label_path = "/w/rest.php/wikibase/v1/entities/items/Q42/labels/en";
+        Mock::given(method("PUT"))
+            .and(path(label_path))
+            .and(header("Authorization", "Bearer alice"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!("Alice's label")),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_oauth2_info("client_id", "client_secret")
+            .with_access_token("tool")
+            .build()
+            .unwrap();
+        let alice_token = api
+            .token
+            .read()
+            .await
+            .for_user(Some("alice".into()), Some("alice_refresh".into()));
+        assert_eq!(alice_token.client_id(), &Some("client_id".into()));
+        // The user's token counts as fresh, so a write does not renew it up front.
+        assert!(!alice_token.needs_renewal(&reqwest::Method::PUT));
+        let alice = api.as_user(alice_token);
+
+        let label = crate::label::Label::new("en", "Alice's label");
+        let id = crate::EntityId::item("Q42");
+        let edited = label.put(&id, &alice).await.unwrap();
+        assert_eq!(edited.value(), "Alice's label");
+        // The context leaves the original API's token alone, and shares its statistics.
+        assert_eq!(api.token.read().await.get(), &Some("tool".into()));
+        assert_eq!(api.stats().total_requests(), 1);
+
+        let shared = Arc::new(RwLock::new(BearerToken::default()));
+        let bob = api.as_user_shared(shared.clone());
+        shared.write().await.set_access_token("bob");
+        assert_eq!(bob.token.read().await.get(), &Some("bob".into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_writes_retried_only_when_guarded() {
+        let mock_server = MockServer::start().await;
+        let mock_path = "/w/rest.php/wikibase/v1/entities/items/Q42/labels/en";
+        Mock::given(method("PUT"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(mock_path))
+            .and(header("If-Match", "\"1234\""))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_max_retries(3)
+            .with_retry_base_delay(Duration::from_millis(1))
+            .build()
+            .unwrap();
+        let url = format!("{}{mock_path}", mock_server.uri());
+
+        // Unguarded, and the revision before the write is unknown: the first PUT may have been
+        // committed, so it is not repeated.
+        let put = api.client().put(&url).build().unwrap();
+        let result = api.execute(put).await;
+        assert!(result.is_err_and(|e| e.is_server_error()));
+
+        // Guarded: a repeat of a committed PATCH would fail with 412, so it is safe.
+        let mut em = crate::EditMetadata::default();
+        em.set_base_revision(1234);
+        let mut patch = api.client().patch(&url).build().unwrap();
+        em.revision_match()
+            .modify_headers(patch.headers_mut())
+            .unwrap();
+        assert_eq!(api.execute(patch).await.unwrap().status(), 200);
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_unguarded_write_retried_with_base_revision() {
+        let mock_server = MockServer::start().await;
+        let mock_path =
+            "/w/rest.php/wikibase/v1/statements/Q42$F078E5B3-F9A8-480E-B7AC-D97778CBBEF9";
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .and(query_param("_fields", "type"))
+            .and(header("Authorization", "Bearer user_token"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"type": "item"}))
+                    .insert_header("ETag", "\"77\""),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(mock_path))
+            .and(header("If-Match", "\"77\""))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_access_token("user_token")
+            .with_retry_policy(
+                crate::ExponentialBackoff::default()
+                    .with_base_delay(Duration::from_millis(1))
+                    .with_read_before_write(true),
+            )
+            .build()
+            .unwrap();
+
+        // The revision is read before the write, and guards the retry.
+        let url = format!("{}{mock_path}", mock_server.uri());
+        let put = api
+            .client()
+            .put(&url)
+            .bearer_auth("user_token")
+            .build()
+            .unwrap();
+        assert_eq!(api.execute(put).await.unwrap().status(), 200);
+        mock_server.verify().await;
+
+        // Without retries, or by default, the revision is not read.
+        mock_server.reset().await;
+        let no_retries = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_retry_policy(
+                crate::ExponentialBackoff::default()
+                    .with_max_retries(0)
+                    .with_read_before_write(true),
+            )
+            .build()
+            .unwrap();
+        let unretried = no_retries.client().put(&url).build().unwrap();
+        no_retries.execute(unretried).await.unwrap();
+        let default = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap();
+        let unguarded = default.client().put(&url).build().unwrap();
+        default.execute(unguarded).await.unwrap();
+        let received = mock_server.received_requests().await.unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|r| r.method.as_str() == "PUT"));
+    }
+
     #[tokio::test]
     #[cfg_attr(miri, ignore)]
     async fn test_retry_exhausted() {
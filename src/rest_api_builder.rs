@@ -1,4 +1,21 @@
-use crate::{bearer_token::BearerToken, RestApi, RestApiError};
+use crate::{
+    bearer_token::{BearerToken, TokenEvent},
+    capabilities::Capabilities,
+    cassette::{Cassette, CassetteTransport},
+    circuit_breaker::{CircuitBreaker, CircuitController},
+    coalesce::Coalescer,
+    concurrency::{AdaptiveConcurrency, ConcurrencyController},
+    dry_run::Plan,
+    middleware::Middleware,
+    rate_limit::{RateLimit, RateLimiter},
+    response_cache::ResponseCache,
+    retry::{ExponentialBackoff, RetryPolicy},
+    schema_validation::SchemaValidator,
+    stats::StatsRecorder,
+    token_store::TokenStore,
+    transport::{ReqwestTransport, Transport},
+    RestApi, RestApiError,
+};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -12,7 +29,9 @@ const WIKIBASE_REST_API_VERSION: u8 = 1;
 #[derive(Debug)]
 pub struct RestApiBuilder {
     client: Option<reqwest::Client>,
+    transport: Option<Arc<dyn Transport>>,
     token: BearerToken,
+    token_store: Option<Arc<dyn TokenStore>>,
     user_agent: Option<String>,
     api_url: String,
     api_version: Option<u8>,
@@ -22,6 +41,18 @@ pub struct RestApiBuilder {
     max_retries: Option<u32>,
     retry_base_delay: Option<Duration>,
     max_retry_after: Option<Duration>,
+    retry_policy: Option<Arc<dyn RetryPolicy>>,
+    cache: Option<ResponseCache>,
+    read_rate_limit: Option<RateLimit>,
+    write_rate_limit: Option<RateLimit>,
+    adaptive_concurrency: Option<AdaptiveConcurrency>,
+    circuit_breaker: Option<CircuitBreaker>,
+    request_coalescing: bool,
+    capabilities: Option<Capabilities>,
+    schema_validation: Option<serde_json::Value>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    cassette: Option<Cassette>,
+    dry_run: bool,
 }
 
 impl RestApiBuilder {
@@ -32,7 +63,9 @@ impl RestApiBuilder {
         let api_url = Self::validate_api_url(&api_url.into())?;
         Ok(Self {
             client: None,
+            transport: None,
             token: BearerToken::default(),
+            token_store: None,
             user_agent: None,
             api_url,
             api_version: None,
@@ -42,6 +75,18 @@ impl RestApiBuilder {
             max_retries: None,
             retry_base_delay: None,
             max_retry_after: None,
+            retry_policy: None,
+            cache: None,
+            read_rate_limit: None,
+            write_rate_limit: None,
+            adaptive_concurrency: None,
+            circuit_breaker: None,
+            request_coalescing: false,
+            capabilities: None,
+            schema_validation: None,
+            middlewares: vec![],
+            cassette: None,
+            dry_run: false,
         })
     }
 
@@ -71,6 +116,102 @@ impl RestApiBuilder {
         self
     }
 
+    /// Sets the transport that sends requests. By default, requests are sent through the
+    /// `reqwest::Client`. Retries, token renewal and `Retry-After` handling still apply.
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets a `ResponseCache` for GET requests. Default is no caching.
+    /// The cache is shared by all clones of the resulting `RestApi`.
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Sets a client-side budget for read (GET) requests. Default is unlimited.
+    /// The budget is shared by all clones of the resulting `RestApi`, and counts every
+    /// request sent, including retries.
+    pub const fn with_read_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.read_rate_limit = Some(limit);
+        self
+    }
+
+    /// Sets a client-side budget for write (POST, PUT, PATCH, DELETE) requests, e.g.
+    /// `RateLimit::per_minute(30)` to honor a bot edit-rate policy. Default is unlimited.
+    /// The budget is shared by all clones of the resulting `RestApi`, and counts every
+    /// request sent, including retries.
+    pub const fn with_write_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.write_rate_limit = Some(limit);
+        self
+    }
+
+    /// Enables an adaptive (AIMD) limit on in-flight requests, shared by all clones of the
+    /// resulting `RestApi` and every `EntityContainer` built on it. The limit grows while the
+    /// server is healthy and shrinks on 429 and 5xx responses. Default is no limit.
+    pub const fn with_adaptive_concurrency(mut self, settings: AdaptiveConcurrency) -> Self {
+        self.adaptive_concurrency = Some(settings);
+        self
+    }
+
+    /// Enables a circuit breaker, shared by all clones of the resulting `RestApi`. After a run
+    /// of consecutive 5xx responses or connection failures, requests fail fast with
+    /// `RestApiError::CircuitOpen` until a probe succeeds. Default is no circuit breaker.
+    pub const fn with_circuit_breaker(mut self, settings: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(settings);
+        self
+    }
+
+    /// Lets concurrent identical GET requests (same URL, conditional headers and token) share
+    /// a single HTTP call, shared by all clones of the resulting `RestApi`. Every caller gets its
+    /// own copy of the response. Default is off.
+    pub const fn with_request_coalescing(mut self, request_coalescing: bool) -> Self {
+        self.request_coalescing = request_coalescing;
+        self
+    }
+
+    /// Sets the `Capabilities` of the API, e.g. saved from an earlier probe, so operations the
+    /// API does not support fail up front with `RestApiError::NotImplementedInRestApi`. See also
+    /// `build_and_probe`. Default is to assume every operation is supported.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Enables checking of JSON payloads against the schemas of an `OpenAPI` document, as
+    /// returned by `RestApi::get_openapi_json`. Request bodies built by this crate are checked
+    /// before they are sent, and response bodies after they arrive; a mismatch fails with
+    /// `RestApiError::SchemaViolations`, listing each offending JSON pointer. Meant for
+    /// debugging, as every response body is read twice. Default is off.
+    pub fn with_schema_validation(mut self, openapi: serde_json::Value) -> Self {
+        self.schema_validation = Some(openapi);
+        self
+    }
+
+    /// Appends a middleware to the chain. Middlewares run in the order they were added, around
+    /// every request sent through `RestApi::execute`. Default is no middleware.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Records every exchange to, or replays it from, a `Cassette` file. In record mode the
+    /// cassette wraps the transport; in replay mode it replaces it, so no request reaches the
+    /// network. Default is no cassette.
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// In dry-run mode, POST, PUT, PATCH and DELETE requests are captured instead of sent, and
+    /// fail with `RestApiError::DryRun` carrying the `PlannedWrite`. GET requests still go
+    /// through. Captured writes are listed by `RestApi::planned_writes`. Default is off.
+    pub const fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
     /// Sets the request timeout. Default is no timeout (reqwest default).
     pub const fn with_timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
@@ -83,14 +224,16 @@ impl RestApiBuilder {
         self
     }
 
-    /// Sets the maximum number of retries on 429/5xx errors. Default is 3.
-    /// Set to 0 to disable retries.
+    /// Sets the maximum number of retries on 429/5xx errors of the default retry policy.
+    /// Default is 3. Set to 0 to disable retries. Writes are only retried on 5xx if that is
+    /// safe, see `EditMetadata::set_base_revision` and `RetryPolicy::reads_before_write`.
     pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
         self.max_retries = Some(max_retries);
         self
     }
 
-    /// Sets the base delay for exponential backoff retries. Default is 1 second.
+    /// Sets the base delay for exponential backoff retries of the default retry policy.
+    /// Default is 1 second.
     /// Actual delay doubles with each retry attempt (1s, 2s, 4s, ...), with ±25% jitter,
     /// unless the server provides a `Retry-After` header.
     pub const fn with_retry_base_delay(mut self, delay: Duration) -> Self {
@@ -106,6 +249,14 @@ impl RestApiBuilder {
         self
     }
 
+    /// Replaces the default retry policy, an `ExponentialBackoff` configured by
+    /// `with_max_retries` and `with_retry_base_delay`, which are then ignored. `Retry-After`
+    /// values passed to the policy are still capped by `with_max_retry_after`.
+    pub fn with_retry_policy<P: RetryPolicy + 'static>(mut self, policy: P) -> Self {
+        self.retry_policy = Some(Arc::new(policy));
+        self
+    }
+
     /// Sets the interval for bearer token renewal. By default, the interval is `DEFAULT_RENEWAL_INTERVAL_SEC`.
     #[cfg(not(tarpaulin_include))]
     pub const fn with_access_token_renewal(mut self, renewal_interval: Duration) -> Self {
@@ -124,17 +275,82 @@ impl RestApiBuilder {
         self
     }
 
+    /// Sets the `OAuth2` client ID of a public client (e.g. a desktop app) that has no client
+    /// secret and authorizes with PKCE, see `BearerToken::start_authorization`.
+    pub fn with_oauth2_public_client<S: Into<String>>(mut self, client_id: S) -> Self {
+        self.token.set_oauth2_public_client(client_id);
+        self
+    }
+
+    /// Sets the `OAuth2` redirect URI, which must match the one registered for the client.
+    pub fn with_oauth2_redirect_uri<S: Into<String>>(mut self, redirect_uri: S) -> Self {
+        self.token.set_redirect_uri(redirect_uri);
+        self
+    }
+
+    /// Sets the `OAuth2` scopes to request, e.g. `["basic", "editpage"]`.
+    pub fn with_oauth2_scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.token.set_scopes(scopes);
+        self
+    }
+
+    /// Sets how long a new `OAuth2` refresh token is valid, for servers that do not say so
+    /// in the token response; see `BearerToken::refresh_token_expires_at`.
+    pub const fn with_refresh_token_lifetime(mut self, lifetime: Duration) -> Self {
+        self.token.set_refresh_token_lifetime(lifetime);
+        self
+    }
+
+    /// Sets a callback for `TokenEvent`s: tokens obtained or renewed, failed renewals, and a
+    /// refresh token about to expire. It runs while the token is locked for writing; see
+    /// `BearerToken::set_event_callback`.
+    pub fn with_token_event_callback<F: Fn(&TokenEvent) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.token.set_event_callback(callback);
+        self
+    }
+
+    /// Logs in with a bot password (from `Special:BotPasswords`) through the `MediaWiki`
+    /// Action API, for wikis without the `OAuth` extension. The login happens before the first
+    /// request; requests are then authorized by the session cookies.
+    pub fn with_bot_password<S1: Into<String>, S2: Into<String>>(
+        mut self,
+        username: S1,
+        password: S2,
+    ) -> Self {
+        self.token.set_bot_password(username, password);
+        self
+    }
+
+    /// Sets a `TokenStore` to load the `OAuth2` tokens from when building, and to save them to
+    /// whenever they are obtained or renewed. Stored tokens replace one set with
+    /// `with_access_token`.
+    pub fn with_token_store<T: TokenStore + 'static>(mut self, store: T) -> Self {
+        self.token_store = Some(Arc::new(store));
+        self
+    }
+
     /// Builds the `RestApi`. The builder gets consumed by this operation.
     ///
     /// # Errors
     /// Returns a `RestApiError` if the HTTP client could not be constructed (e.g. TLS
     /// initialization failure); configured timeouts are never silently discarded.
+    /// Also fails if a replay cassette or the token store cannot be read.
     pub fn build(self) -> Result<RestApi, RestApiError> {
         let api_url = self.api_url;
         let mut token = self.token;
         if let Some(interval) = self.renewal_interval {
             token.set_renewal_interval(interval.as_secs());
         }
+        if let Some(store) = self.token_store {
+            token.set_token_store(store)?;
+        }
         let token = Arc::new(RwLock::new(token));
         let user_agent = self.user_agent.unwrap_or_else(Self::default_user_agent);
         let api_version = self.api_version.unwrap_or(WIKIBASE_REST_API_VERSION);
@@ -145,6 +361,14 @@ impl RestApiBuilder {
         let max_retry_after = self
             .max_retry_after
             .unwrap_or(RestApi::default_max_retry_after());
+        let retry_policy = self.retry_policy.unwrap_or_else(|| {
+            Arc::new(
+                ExponentialBackoff::default()
+                    .with_max_retries(max_retries)
+                    .with_base_delay(retry_base_delay)
+                    .with_max_delay(max_retry_after),
+            )
+        });
         let client = match self.client {
             Some(client) => client,
             None => {
@@ -158,8 +382,21 @@ impl RestApiBuilder {
                 builder.build()?
             }
         };
+        let schema_validator = self.schema_validation.map(|openapi| {
+            let root = reqwest::Url::parse(&api_url)
+                .map(|url| format!("{}/wikibase", url.path()))
+                .unwrap_or_default();
+            Arc::new(SchemaValidator::new(openapi, root))
+        });
+        let mut transport = self
+            .transport
+            .unwrap_or_else(|| Arc::new(ReqwestTransport::new(client.clone())));
+        if let Some(cassette) = self.cassette {
+            transport = Arc::new(CassetteTransport::new(cassette, transport)?);
+        }
         Ok(RestApi::new(
             client,
+            transport,
             user_agent,
             api_url,
             api_version,
@@ -167,9 +404,40 @@ impl RestApiBuilder {
             max_retries,
             retry_base_delay,
             max_retry_after,
+            retry_policy,
+            self.cache,
+            Arc::new(RateLimiter::new(
+                self.read_rate_limit,
+                self.write_rate_limit,
+            )),
+            self.adaptive_concurrency
+                .map(|settings| Arc::new(ConcurrencyController::new(settings))),
+            self.circuit_breaker
+                .map(|settings| Arc::new(CircuitController::new(settings))),
+            self.request_coalescing
+                .then(|| Arc::new(Coalescer::default())),
+            self.capabilities.map(Arc::new),
+            schema_validator,
+            self.middlewares.into(),
+            Arc::new(StatsRecorder::default()),
+            Arc::new(Plan::new(self.dry_run)),
         ))
     }
 
+    /// Builds the `RestApi`, then fetches its `OpenAPI` document, so operations the API does not
+    /// support fail up front with `RestApiError::NotImplementedInRestApi`. Capabilities set
+    /// with `with_capabilities` are replaced.
+    ///
+    /// # Errors
+    /// Returns a `RestApiError` if `build` fails, or if the `OpenAPI` document cannot be
+    /// fetched or parsed.
+    pub async fn build_and_probe(mut self) -> Result<RestApi, RestApiError> {
+        self.capabilities = None;
+        let api = self.build()?;
+        let capabilities = api.probe_capabilities().await?;
+        Ok(api.with_capabilities(capabilities))
+    }
+
     /// Checks if the REST API URL is valid. The URL must end in "rest.php".
     /// Removes anything beyond that.
     fn validate_api_url(api_url: &str) -> Result<String, RestApiError> {
@@ -276,6 +544,124 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_debug_redacts_credentials() {
+        let builder = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_oauth2_info("client_id", "client_secret_value")
+            .with_access_token("access_token_value")
+            .with_bot_password("User@Bot", "bot_password_value");
+        let api = builder.build().unwrap();
+        api.token()
+            .write()
+            .await
+            .set_tokens(None, Some("refresh_token_value".into()));
+        let debug = format!("{api:?}");
+        assert!(debug.contains("client_id"));
+        assert!(debug.contains("[REDACTED]"));
+        for secret in [
+            "client_secret_value",
+            "access_token_value",
+            "bot_password_value",
+            "refresh_token_value",
+        ] {
+            assert!(!debug.contains(secret), "{secret} leaked");
+        }
+        let builder_debug = format!(
+            "{:?}",
+            RestApi::builder("https://test.wikidata.org/w/rest.php")
+                .unwrap()
+                .with_access_token("access_token_value")
+        );
+        assert!(!builder_debug.contains("access_token_value"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_with_token_store() {
+        let fresh = crate::MemoryTokenStore::with_tokens(
+            crate::StoredTokens::new(
+                Some("stored_access".into()),
+                Some("stored_refresh".into()),
+                Some(Duration::from_secs(600)),
+            )
+            .with_granted_scopes(["basic"]),
+        );
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_oauth2_info("client_id", "client_secret")
+            .with_access_token("replaced")
+            .with_token_store(fresh)
+            .build()
+            .unwrap();
+        let token = api.token();
+        let token = token.read().await;
+        assert_eq!(token.get(), &Some("stored_access".into()));
+        assert_eq!(token.refresh_token(), &Some("stored_refresh".into()));
+        assert_eq!(token.granted_scopes(), ["basic"]);
+        assert!(!token.needs_renewal(&reqwest::Method::POST));
+
+        // A stored token of unknown age is renewed before the first write.
+        let unknown_age = crate::MemoryTokenStore::with_tokens(crate::StoredTokens::new(
+            Some("stored_access".into()),
+            Some("stored_refresh".into()),
+            None,
+        ));
+        let api2 = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_oauth2_info("client_id", "client_secret")
+            .with_token_store(unknown_age)
+            .build()
+            .unwrap();
+        assert!(api2
+            .token()
+            .read()
+            .await
+            .needs_renewal(&reqwest::Method::POST));
+
+        // An empty store leaves the token as configured.
+        let api3 = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_access_token("configured")
+            .with_token_store(crate::MemoryTokenStore::new())
+            .build()
+            .unwrap();
+        assert_eq!(api3.token().read().await.get(), &Some("configured".into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_with_token_event_callback() {
+        let refresh_expires_at = std::time::SystemTime::now() + Duration::from_secs(3600);
+        let store = crate::MemoryTokenStore::with_tokens(
+            crate::StoredTokens::new(
+                Some("stored_access".into()),
+                Some("stored_refresh".into()),
+                Some(Duration::from_secs(600)),
+            )
+            .with_expiry(None, Some(refresh_expires_at)),
+        );
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = events.clone();
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_oauth2_info("client_id", "client_secret")
+            .with_refresh_token_lifetime(Duration::from_secs(365 * 24 * 60 * 60))
+            .with_token_event_callback(move |event| recorded.lock().unwrap().push(event.clone()))
+            .with_token_store(store)
+            .build()
+            .unwrap();
+
+        // A stored refresh token that expires soon is reported when it is loaded.
+        let expires_at = api.token().read().await.refresh_token_expires_at().unwrap();
+        assert!(expires_at <= refresh_expires_at);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![TokenEvent::RefreshTokenExpiring { expires_at }]
+        );
+    }
+
     #[tokio::test]
     #[cfg_attr(miri, ignore)]
     async fn test_with_oauth2_info() {
@@ -293,11 +679,11 @@ mod tests {
             .unwrap();
         assert_eq!(
             *api2.token().read().await.client_id(),
-            Some("client_id".to_string())
+            Some("client_id".into())
         );
         assert_eq!(
             *api2.token().read().await.client_secret(),
-            Some("client_secret".to_string())
+            Some("client_secret".into())
         );
     }
 
@@ -318,6 +704,8 @@ mod tests {
         // Covers the struct literal in `new()`: every optional field starts unset.
         let builder = RestApiBuilder::new("https://www.wikidata.org/w/rest.php").unwrap();
         assert!(builder.client.is_none());
+        assert!(builder.transport.is_none());
+        assert!(builder.token_store.is_none());
         assert!(builder.user_agent.is_none());
         assert!(builder.api_version.is_none());
         assert!(builder.renewal_interval.is_none());
@@ -326,6 +714,13 @@ mod tests {
         assert!(builder.max_retries.is_none());
         assert!(builder.retry_base_delay.is_none());
         assert!(builder.max_retry_after.is_none());
+        assert!(builder.cache.is_none());
+        assert!(builder.read_rate_limit.is_none());
+        assert!(builder.write_rate_limit.is_none());
+        assert!(builder.adaptive_concurrency.is_none());
+        assert!(builder.middlewares.is_empty());
+        assert!(builder.cassette.is_none());
+        assert!(!builder.dry_run);
         assert_eq!(builder.api_url, "https://www.wikidata.org/w/rest.php");
     }
 
@@ -0,0 +1,417 @@
+use crate::RestApi;
+use reqwest::{Method, StatusCode};
+use std::{
+    fmt::Debug,
+    time::{Duration, SystemTime},
+};
+
+/// What a `RetryPolicy` knows about a failed attempt.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryContext<'a> {
+    method: &'a Method,
+    status: StatusCode,
+    error_code: Option<&'a str>,
+    attempt: u32,
+    elapsed: Duration,
+    retry_after: Option<Duration>,
+    safe_to_repeat: bool,
+}
+
+impl<'a> RetryContext<'a> {
+    /// Creates a context for the first attempt of a request that got `status`.
+    pub const fn new(method: &'a Method, status: StatusCode) -> Self {
+        Self {
+            method,
+            status,
+            error_code: None,
+            attempt: 0,
+            elapsed: Duration::ZERO,
+            retry_after: None,
+            safe_to_repeat: false,
+        }
+    }
+
+    /// Sets the `code` of the error payload, e.g. `"resource-not-found"`.
+    pub const fn with_error_code(mut self, error_code: Option<&'a str>) -> Self {
+        self.error_code = error_code;
+        self
+    }
+
+    /// Sets the number of the failed attempt, starting at 0.
+    pub const fn with_attempt(mut self, attempt: u32) -> Self {
+        self.attempt = attempt;
+        self
+    }
+
+    /// Sets the time since the first attempt was started.
+    pub const fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Sets the delay the server asked for in `Retry-After`.
+    pub const fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Sets whether the request can be sent again without the risk of applying an edit twice.
+    pub const fn with_safe_to_repeat(mut self, safe_to_repeat: bool) -> Self {
+        self.safe_to_repeat = safe_to_repeat;
+        self
+    }
+
+    /// Returns the HTTP method of the request.
+    pub const fn method(&self) -> &Method {
+        self.method
+    }
+
+    /// Returns the HTTP status of the response.
+    pub const fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the `code` of the error payload, if the response had one.
+    pub const fn error_code(&self) -> Option<&str> {
+        self.error_code
+    }
+
+    /// Returns the number of the failed attempt, starting at 0.
+    pub const fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Returns the time since the first attempt was started.
+    pub const fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the delay the server asked for in `Retry-After`, capped at
+    /// `RestApiBuilder::with_max_retry_after`.
+    pub const fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Returns `true` if sending the request again cannot apply an edit twice: reads, writes
+    /// guarded by `If-Match`, and statement POSTs that were checked not to have landed.
+    pub const fn safe_to_repeat(&self) -> bool {
+        self.safe_to_repeat
+    }
+
+    /// Returns `true` for a 429, or for a 5xx if the request is safe to repeat. This is what
+    /// the built-in policies retry.
+    pub fn is_transient(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS
+            || (self.status.is_server_error() && self.safe_to_repeat)
+    }
+}
+
+/// Decides whether a failed request is retried, and after how long.
+///
+/// `RestApi` asks the policy after every response with a 4xx or 5xx status. Set it with
+/// `RestApiBuilder::with_retry_policy`; the default is `ExponentialBackoff`.
+///
+/// ```
+/// use std::time::Duration;
+/// use wikibase_rest_api::prelude::*;
+///
+/// /// Retries 412s once, quickly, and everything else like the default policy.
+/// #[derive(Debug)]
+/// struct RetryPreconditions(ExponentialBackoff);
+///
+/// impl RetryPolicy for RetryPreconditions {
+///     fn retry_delay(&self, context: &RetryContext) -> Option<Duration> {
+///         if context.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+///             return (context.attempt() == 0).then_some(Duration::from_millis(100));
+///         }
+///         self.0.retry_delay(context)
+///     }
+/// }
+///
+/// let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+///     .unwrap()
+///     .with_retry_policy(RetryPreconditions(
+///         ExponentialBackoff::default().with_deadline(Duration::from_secs(30)),
+///     ))
+///     .build()
+///     .unwrap();
+/// ```
+pub trait RetryPolicy: Debug + Send + Sync {
+    /// Returns the delay before the next attempt, or `None` to give up and return the response.
+    fn retry_delay(&self, context: &RetryContext) -> Option<Duration>;
+
+    /// Returns `true` to read the revision of an entity before a write to it that is not safe
+    /// to repeat (a PUT, PATCH or DELETE without `If-Match`, or `Statements::post_meta`), so
+    /// that it can be guarded with `If-Match` and retried after a 5xx. This costs one GET per
+    /// such write. Default is `false`: those writes are sent once, unless the caller set
+    /// `EditMetadata::set_base_revision`.
+    fn reads_before_write(&self) -> bool {
+        false
+    }
+}
+
+/// Returns `delay`, unless waiting that long would pass `deadline`.
+fn within_deadline(
+    context: &RetryContext,
+    delay: Duration,
+    deadline: Option<Duration>,
+) -> Option<Duration> {
+    match deadline {
+        Some(deadline) if context.elapsed().saturating_add(delay) > deadline => None,
+        _ => Some(delay),
+    }
+}
+
+/// Retries transient errors with exponential backoff and ±25% jitter, honoring `Retry-After`.
+/// This is the default policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    deadline: Option<Duration>,
+    read_before_write: bool,
+}
+
+impl Default for ExponentialBackoff {
+    /// 3 retries, starting at 1 second, at most 60 seconds each, no deadline, no reads before
+    /// writes.
+    fn default() -> Self {
+        Self {
+            max_retries: RestApi::default_max_retries(),
+            base_delay: RestApi::default_retry_base_delay(),
+            max_delay: RestApi::default_max_retry_after(),
+            deadline: None,
+            read_before_write: false,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    /// Sets the maximum number of retries.
+    pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry; it doubles with every further retry.
+    pub const fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Sets the upper bound of the backoff delay.
+    pub const fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Gives up once the next attempt would start more than `deadline` after the first.
+    pub const fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Reads the revision before unguarded writes, so they can be retried after a 5xx, see
+    /// `RetryPolicy::reads_before_write`. Default is `false`.
+    pub const fn with_read_before_write(mut self, read_before_write: bool) -> Self {
+        self.read_before_write = read_before_write;
+        self
+    }
+
+    /// Returns the maximum number of retries.
+    pub const fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns the delay before the first retry.
+    pub const fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    /// Exponential backoff with ±25% jitter, capped at `max_delay`.
+    /// Jitter avoids synchronized retry storms when many clients back off together.
+    fn backoff_with_jitter(&self, attempt: u32) -> Duration {
+        let multiplier = 2_u32.saturating_pow(attempt.min(16));
+        let base = self
+            .base_delay
+            .saturating_mul(multiplier)
+            .min(self.max_delay);
+        let factor = 0.75 + jitter_fraction() * 0.5; // [0.75, 1.25)
+        base.mul_f64(factor)
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn retry_delay(&self, context: &RetryContext) -> Option<Duration> {
+        if !context.is_transient() || context.attempt() >= self.max_retries {
+            return None;
+        }
+        let delay = context
+            .retry_after()
+            .unwrap_or_else(|| self.backoff_with_jitter(context.attempt()));
+        within_deadline(context, delay, self.deadline)
+    }
+
+    fn reads_before_write(&self) -> bool {
+        self.read_before_write
+    }
+}
+
+/// Retries transient errors after a fixed delay, or after `Retry-After` if that is longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedDelay {
+    delay: Duration,
+    max_retries: u32,
+    deadline: Option<Duration>,
+    read_before_write: bool,
+}
+
+impl FixedDelay {
+    /// Retries up to 3 times, `delay` apart.
+    pub const fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            max_retries: RestApi::default_max_retries(),
+            deadline: None,
+            read_before_write: false,
+        }
+    }
+
+    /// Sets the maximum number of retries.
+    pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Gives up once the next attempt would start more than `deadline` after the first.
+    pub const fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Reads the revision before unguarded writes, so they can be retried after a 5xx, see
+    /// `RetryPolicy::reads_before_write`. Default is `false`.
+    pub const fn with_read_before_write(mut self, read_before_write: bool) -> Self {
+        self.read_before_write = read_before_write;
+        self
+    }
+}
+
+impl RetryPolicy for FixedDelay {
+    fn retry_delay(&self, context: &RetryContext) -> Option<Duration> {
+        if !context.is_transient() || context.attempt() >= self.max_retries {
+            return None;
+        }
+        let delay = context
+            .retry_after()
+            .map_or(self.delay, |retry_after| retry_after.max(self.delay));
+        within_deadline(context, delay, self.deadline)
+    }
+
+    fn reads_before_write(&self) -> bool {
+        self.read_before_write
+    }
+}
+
+/// Never retries; every error is returned right away.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NeverRetry;
+
+impl RetryPolicy for NeverRetry {
+    fn retry_delay(&self, _context: &RetryContext) -> Option<Duration> {
+        None
+    }
+}
+
+/// A pseudo-random fraction in [0, 1) derived from the current time. Good enough for
+/// jitter; deliberately dependency-free (no `rand`).
+fn jitter_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    f64::from(nanos % 1000) / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(method: &Method, status: u16) -> RetryContext<'_> {
+        RetryContext::new(method, StatusCode::from_u16(status).unwrap())
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(context(&Method::POST, 429).is_transient());
+        assert!(!context(&Method::POST, 503).is_transient());
+        assert!(context(&Method::GET, 503)
+            .with_safe_to_repeat(true)
+            .is_transient());
+        assert!(!context(&Method::GET, 404)
+            .with_safe_to_repeat(true)
+            .is_transient());
+    }
+
+    #[test]
+    fn test_exponential_retry_after() {
+        let policy = ExponentialBackoff::default();
+        let ctx = context(&Method::GET, 429).with_retry_after(Some(Duration::from_secs(10)));
+        assert_eq!(policy.retry_delay(&ctx), Some(Duration::from_secs(10)));
+        assert_eq!(policy.retry_delay(&ctx.with_attempt(3)), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // jitter_fraction() uses the realtime clock, unavailable under miri
+    fn test_backoff_with_jitter_bounds() {
+        let policy = ExponentialBackoff::default().with_base_delay(Duration::from_secs(1));
+        // Attempt 2 => base 4s, jittered into [3s, 5s).
+        let delay = policy.backoff_with_jitter(2);
+        assert!(delay >= Duration::from_secs(3) && delay < Duration::from_secs(5));
+        let capped = policy.with_max_delay(Duration::from_secs(2));
+        assert!(capped.backoff_with_jitter(10) < Duration::from_millis(2500));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // jitter_fraction() uses the realtime clock, unavailable under miri
+    fn test_jitter_fraction_range() {
+        let f = jitter_fraction();
+        assert!((0.0..1.0).contains(&f));
+    }
+
+    #[test]
+    fn test_deadline() {
+        let policy = FixedDelay::new(Duration::from_secs(5)).with_deadline(Duration::from_secs(20));
+        let ctx = context(&Method::GET, 503).with_safe_to_repeat(true);
+        assert_eq!(
+            policy.retry_delay(&ctx.with_elapsed(Duration::from_secs(15))),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policy.retry_delay(&ctx.with_elapsed(Duration::from_secs(16))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fixed_delay_prefers_longer_retry_after() {
+        let policy = FixedDelay::new(Duration::from_secs(5)).with_max_retries(1);
+        let ctx = context(&Method::GET, 429);
+        assert_eq!(policy.retry_delay(&ctx), Some(Duration::from_secs(5)));
+        assert_eq!(
+            policy.retry_delay(&ctx.with_retry_after(Some(Duration::from_secs(1)))),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policy.retry_delay(&ctx.with_retry_after(Some(Duration::from_secs(9)))),
+            Some(Duration::from_secs(9))
+        );
+        assert_eq!(policy.retry_delay(&ctx.with_attempt(1)), None);
+    }
+
+    #[test]
+    fn test_never_retry() {
+        let ctx = context(&Method::GET, 429);
+        assert_eq!(NeverRetry.retry_delay(&ctx), None);
+    }
+}
@@ -0,0 +1,594 @@
+use crate::{capabilities::Capabilities, coalesce::BufferedResponse, RestApiError};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+};
+
+/// Whether a payload was sent or received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// A request body, checked before it is sent.
+    Request,
+    /// A response body, with the HTTP status it came with.
+    Response(reqwest::StatusCode),
+}
+
+/// A place where a payload does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pointer: String,
+    expected: String,
+    found: String,
+}
+
+impl SchemaViolation {
+    /// Returns the JSON pointer to the offending value, e.g. `/statement/rank`. The empty
+    /// string is the whole payload.
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    /// Returns what the schema expects, e.g. `object` or `one of ["normal", "preferred"]`.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// Returns what was found instead, e.g. `array` or `missing`.
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(
+            f,
+            "{pointer}: expected {}, found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+/// All schema violations of one payload, see `RestApiError::SchemaViolations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaReport {
+    kind: PayloadKind,
+    method: reqwest::Method,
+    path: String,
+    violations: Vec<SchemaViolation>,
+}
+
+impl SchemaReport {
+    /// Returns whether the request or the response body was checked.
+    pub const fn kind(&self) -> PayloadKind {
+        self.kind
+    }
+
+    /// Returns the HTTP method of the request.
+    pub const fn method(&self) -> &reqwest::Method {
+        &self.method
+    }
+
+    /// Returns the path of the request, relative to the Wikibase REST API root, e.g.
+    /// `/v1/entities/items/Q42`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the violations.
+    pub fn violations(&self) -> &[SchemaViolation] {
+        &self.violations
+    }
+}
+
+impl Display for SchemaReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            PayloadKind::Request => "request".to_string(),
+            PayloadKind::Response(status) => format!("{} response", status.as_u16()),
+        };
+        write!(f, "{kind} of {} {}:", self.method, self.path)?;
+        for violation in &self.violations {
+            write!(f, " {violation};")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks JSON payloads against the schemas of an `OpenAPI` document. Only the parts of JSON
+/// Schema the Wikibase REST API uses are supported: `type`, `properties`, `required`,
+/// `additionalProperties`, `items`, `enum`, `const`, `pattern`, `allOf`, `anyOf`, `oneOf`
+/// (exactly one alternative must match) and local `$ref`s. Set with
+/// `RestApiBuilder::with_schema_validation`.
+#[derive(Debug)]
+pub(crate) struct SchemaValidator {
+    openapi: Value,
+    /// The URL path of the Wikibase REST API root, e.g. `/w/rest.php/wikibase`.
+    root: String,
+    /// Compiled `pattern`s, by pattern; `None` for a pattern that does not compile, which is
+    /// not checked.
+    regexes: Mutex<HashMap<String, Option<regex::Regex>>>,
+}
+
+impl SchemaValidator {
+    pub(crate) fn new(openapi: Value, root: String) -> Self {
+        Self {
+            openapi,
+            root,
+            regexes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `false` if `s` does not match `pattern`, compiling each pattern only once.
+    fn matches_pattern(&self, pattern: &str, s: &str) -> bool {
+        let Ok(mut regexes) = self.regexes.lock() else {
+            return true;
+        };
+        regexes
+            .entry(pattern.to_string())
+            .or_insert_with(|| regex::Regex::new(pattern).ok())
+            .as_ref()
+            .is_none_or(|re| re.is_match(s))
+    }
+
+    /// Checks the JSON body of `request`, if the operation has a request schema.
+    pub(crate) fn check_request(&self, request: &reqwest::Request) -> Result<(), RestApiError> {
+        let Some(body) = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .and_then(|bytes| serde_json::from_slice::<Value>(bytes).ok())
+        else {
+            return Ok(());
+        };
+        let Some((path, operation)) = self.operation(request.method(), request.url()) else {
+            return Ok(());
+        };
+        let Some(schema) = Self::json_schema(&operation["requestBody"]) else {
+            return Ok(());
+        };
+        self.report(PayloadKind::Request, request.method(), path, schema, &body)
+    }
+
+    /// Checks the JSON body of `response`, if the operation has a schema for its status.
+    /// Returns an equivalent response, as the body has to be read.
+    pub(crate) async fn check_response(
+        &self,
+        method: &reqwest::Method,
+        url: &reqwest::Url,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, RestApiError> {
+        let status = response.status();
+        let Some((path, operation)) = self.operation(method, url) else {
+            return Ok(response);
+        };
+        let responses = &operation["responses"];
+        let documented = responses
+            .get(status.as_str())
+            .or_else(|| responses.get("default"));
+        let Some(schema) = documented.and_then(Self::json_schema) else {
+            return Ok(response);
+        };
+        let buffered = BufferedResponse::read(response).await?;
+        if let Ok(body) = serde_json::from_slice::<Value>(buffered.body()) {
+            self.report(PayloadKind::Response(status), method, path, schema, &body)?;
+        }
+        Ok(buffered.to_response())
+    }
+
+    fn report(
+        &self,
+        kind: PayloadKind,
+        method: &reqwest::Method,
+        path: String,
+        schema: &Value,
+        body: &Value,
+    ) -> Result<(), RestApiError> {
+        let mut violations = vec![];
+        self.validate(schema, body, "", kind, &mut violations);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(RestApiError::SchemaViolations(Box::new(SchemaReport {
+            kind,
+            method: method.clone(),
+            path,
+            violations,
+        })))
+    }
+
+    /// Finds the operation for a request URL, and returns it with the relative path.
+    fn operation(&self, method: &reqwest::Method, url: &reqwest::Url) -> Option<(String, &Value)> {
+        let path = url.path().strip_prefix(&self.root)?;
+        let method = method.as_str().to_ascii_lowercase();
+        self.openapi["paths"]
+            .as_object()?
+            .iter()
+            .filter(|(template, _)| Capabilities::path_matches(template, path))
+            .find_map(|(_, operations)| operations.get(&method))
+            .map(|operation| (path.to_string(), operation))
+    }
+
+    /// Returns the JSON schema of a request body or response object.
+    fn json_schema(object: &Value) -> Option<&Value> {
+        let content = object["content"].as_object()?;
+        let media = content
+            .get("application/json")
+            .or_else(|| content.values().next())?;
+        media.get("schema")
+    }
+
+    fn resolve<'a>(&'a self, schema: &'a Value) -> &'a Value {
+        schema["$ref"]
+            .as_str()
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| self.openapi.pointer(pointer))
+            .unwrap_or(schema)
+    }
+
+    fn validate(
+        &self,
+        schema: &Value,
+        value: &Value,
+        pointer: &str,
+        kind: PayloadKind,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        let schema = self.resolve(schema);
+        let mut violate = |expected: String, found: String| {
+            violations.push(SchemaViolation {
+                pointer: pointer.to_string(),
+                expected,
+                found,
+            });
+        };
+        if let Some(types) = Self::types(&schema["type"]) {
+            if !types.iter().any(|t| Self::has_type(value, t)) {
+                violate(types.join(" or "), Self::type_name(value).to_string());
+                return;
+            }
+        }
+        if let Some(options) = schema["enum"].as_array() {
+            if !options.contains(value) {
+                violate(format!("one of {}", schema["enum"]), value.to_string());
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                violate(constant.to_string(), value.to_string());
+            }
+        }
+        if let (Some(pattern), Some(s)) = (schema["pattern"].as_str(), value.as_str()) {
+            if !self.matches_pattern(pattern, s) {
+                violate(format!("string matching {pattern}"), value.to_string());
+            }
+        }
+        for alternatives in ["anyOf", "oneOf"] {
+            if let Some(options) = schema[alternatives].as_array() {
+                let matching = options
+                    .iter()
+                    .filter(|option| {
+                        let mut scratch = vec![];
+                        self.validate(option, value, pointer, kind, &mut scratch);
+                        scratch.is_empty()
+                    })
+                    .count();
+                if matching == 0 {
+                    violate(
+                        format!("{alternatives} of {} schemas", options.len()),
+                        Self::type_name(value).to_string(),
+                    );
+                } else if matching > 1 && alternatives == "oneOf" {
+                    violate(
+                        format!("oneOf of {} schemas", options.len()),
+                        format!("{} matching {matching}", Self::type_name(value)),
+                    );
+                }
+            }
+        }
+        for part in schema["allOf"].as_array().into_iter().flatten() {
+            self.validate(part, value, pointer, kind, violations);
+        }
+        if let Some(object) = value.as_object() {
+            self.validate_object(schema, object, pointer, kind, violations);
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (index, item) in array.iter().enumerate() {
+                self.validate(items, item, &format!("{pointer}/{index}"), kind, violations);
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        schema: &Value,
+        object: &Map<String, Value>,
+        pointer: &str,
+        kind: PayloadKind,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        let properties = schema["properties"].as_object();
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let Some(key) = required.as_str() else {
+                continue;
+            };
+            // Read-only properties are only required in responses.
+            let read_only = properties
+                .and_then(|properties| properties.get(key))
+                .is_some_and(|property| self.resolve(property)["readOnly"] == true);
+            let exempt = read_only && kind == PayloadKind::Request;
+            if !object.contains_key(key) && !exempt {
+                violations.push(SchemaViolation {
+                    pointer: format!("{pointer}/{}", Self::escape(key)),
+                    expected: "required property".to_string(),
+                    found: "missing".to_string(),
+                });
+            }
+        }
+        for (key, value) in object {
+            let child = format!("{pointer}/{}", Self::escape(key));
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.validate(property, value, &child, kind, violations),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => violations.push(SchemaViolation {
+                        pointer: child,
+                        expected: "no additional property".to_string(),
+                        found: Self::type_name(value).to_string(),
+                    }),
+                    Some(additional @ Value::Object(_)) => {
+                        self.validate(additional, value, &child, kind, violations);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn types(type_value: &Value) -> Option<Vec<&str>> {
+        match type_value {
+            Value::String(t) => Some(vec![t.as_str()]),
+            Value::Array(types) => Some(types.iter().filter_map(Value::as_str).collect()),
+            _ => None,
+        }
+    }
+
+    fn has_type(value: &Value, type_name: &str) -> bool {
+        match type_name {
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            _ => Self::type_name(value) == type_name,
+        }
+    }
+
+    const fn type_name(value: &Value) -> &'static str {
+        match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// Escapes a key for a JSON pointer (RFC 6901).
+    fn escape(key: &str) -> String {
+        key.replace('~', "~0").replace('/', "~1")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        entity::Entity, statement_rank::StatementRank, statements::Statements, EntityId, Item,
+        RestApi, Statement,
+    };
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn openapi() -> Value {
+        serde_json::from_str(&std::fs::read_to_string("spec/openapi.json").unwrap()).unwrap()
+    }
+
+    fn violations(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+        let validator = SchemaValidator::new(json!({}), String::new());
+        let mut violations = vec![];
+        validator.validate(schema, value, "", PayloadKind::Request, &mut violations);
+        violations
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "rank": {"type": "string", "enum": ["normal", "preferred"]},
+                "id": {"type": "string", "readOnly": true},
+                "parts": {"type": "array", "items": {"type": "integer"}},
+                "a/b": {"type": ["string", "null"]}
+            },
+            "required": ["rank", "id", "parts"],
+            "additionalProperties": false
+        });
+        let valid = json!({"rank": "normal", "parts": [1, 2], "a/b": null});
+        assert!(violations(&schema, &valid).is_empty());
+
+        let invalid = json!({"rank": "best", "parts": [1, "2"], "a/b": 3, "extra": true});
+        let found: Vec<String> = violations(&schema, &invalid)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "/a~1b: expected string or null, found number",
+                "/extra: expected no additional property, found boolean",
+                "/parts/1: expected integer, found string",
+                "/rank: expected one of [\"normal\",\"preferred\"], found \"best\"",
+            ]
+        );
+        let missing = violations(&schema, &json!({"rank": "normal"}));
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].pointer(), "/parts");
+        assert_eq!(missing[0].found(), "missing");
+        assert_eq!(violations(&schema, &json!([]))[0].expected(), "object");
+    }
+
+    #[test]
+    fn test_validate_alternatives() {
+        let schema = json!({
+            "oneOf": [{"type": "string", "pattern": "^[PQ][1-9]\\d*$"}, {"type": "integer"}]
+        });
+        assert!(violations(&schema, &json!("Q42")).is_empty());
+        assert!(violations(&schema, &json!(42)).is_empty());
+        let unmatched: Vec<String> = violations(&schema, &json!("L42"))
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            unmatched,
+            vec!["/: expected oneOf of 2 schemas, found string"]
+        );
+
+        let overlapping = json!({"oneOf": [{"type": "number"}, {"type": "integer"}]});
+        let ambiguous: Vec<String> = violations(&overlapping, &json!(42))
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            ambiguous,
+            vec!["/: expected oneOf of 2 schemas, found number matching 2"]
+        );
+        let any = json!({"anyOf": [{"type": "number"}, {"type": "integer"}]});
+        assert!(violations(&any, &json!(42)).is_empty());
+        assert_eq!(violations(&any, &json!("42")).len(), 1);
+    }
+
+    #[test]
+    fn test_patterns_are_compiled_once() {
+        let validator = SchemaValidator::new(json!({}), String::new());
+        let schema = json!({"type": "array", "items": {"type": "string", "pattern": "^Q\\d+$"}});
+        let mut violations = vec![];
+        let value = json!(["Q1", "Q2", "P3"]);
+        validator.validate(&schema, &value, "", PayloadKind::Request, &mut violations);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].pointer(), "/2");
+        assert_eq!(validator.regexes.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_request_violation() {
+        // A schema that only accepts preferred statements, to provoke a violation.
+        let mut doc = openapi();
+        *doc.pointer_mut(
+            "/paths/~1v1~1entities~1items~1{item_id}~1statements/post/requestBody/content\
+             /application~1json/schema/allOf/0/properties/statement/allOf/0/allOf/0\
+             /properties/rank/enum",
+        )
+        .unwrap() = json!(["preferred"]);
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_schema_validation(doc)
+            .build()
+            .unwrap();
+
+        let mut statement = Statement::new_string("P31", "Q5");
+        statement.set_rank(StatementRank::Normal);
+        let result = Statements::default()
+            .post(&EntityId::item("Q42"), statement, &api)
+            .await;
+        let Err(RestApiError::SchemaViolations(report)) = result else {
+            panic!("expected a schema violation, got {result:?}");
+        };
+        assert_eq!(report.kind(), PayloadKind::Request);
+        assert_eq!(report.path(), "/v1/entities/items/Q42/statements");
+        assert_eq!(report.violations().len(), 1);
+        assert_eq!(report.violations()[0].pointer(), "/statement/rank");
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_valid_item_post() {
+        let v: Value =
+            serde_json::from_str(&std::fs::read_to_string("test_data/Q42.json").unwrap()).unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&v))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_schema_validation(openapi())
+            .build()
+            .unwrap();
+
+        // A new item with all the content of Q42.
+        let q42 = Item::from_json(v).unwrap();
+        let mut item = Item::default();
+        *item.labels_mut() = q42.labels().clone();
+        *item.descriptions_mut() = q42.descriptions().clone();
+        *item.aliases_mut() = q42.aliases().clone();
+        *item.sitelinks_mut() = q42.sitelinks().clone();
+        *item.statements_mut() = q42.statements().clone();
+        let posted = item.post(&api).await.unwrap();
+        assert_eq!(posted.id(), &EntityId::item("Q42"));
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_response_violation() {
+        let mut v: Value =
+            serde_json::from_str(&std::fs::read_to_string("test_data/Q42.json").unwrap()).unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&v))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        v["labels"] = json!(["Douglas Adams"]);
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&v))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(mock_server.uri() + "/w/rest.php")
+            .unwrap()
+            .with_schema_validation(openapi())
+            .build()
+            .unwrap();
+
+        // The real payload passes.
+        assert!(Item::get(EntityId::item("Q42"), &api).await.is_ok());
+        let result = Item::get(EntityId::item("Q42"), &api).await;
+        let Err(RestApiError::SchemaViolations(report)) = result else {
+            panic!("expected a schema violation, got {result:?}");
+        };
+        assert_eq!(
+            report.kind(),
+            PayloadKind::Response(reqwest::StatusCode::OK)
+        );
+        assert_eq!(report.violations()[0].pointer(), "/labels");
+        assert_eq!(report.violations()[0].expected(), "object");
+        assert_eq!(report.violations()[0].found(), "array");
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{entity::EntityType, Language, RestApi, RestApiError};
+use crate::{
+    endpoints::{Endpoint, SearchItem},
+    entity::EntityType,
+    Language, RestApi, RestApiError,
+};
 use nutype::nutype;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -157,7 +161,7 @@ impl Search {
     }
 
     async fn generate_json_request(&self, api: &RestApi) -> Result<reqwest::Request, RestApiError> {
-        let path = self.get_my_rest_api_path();
+        let path = self.get_my_rest_api_path()?;
         let mut params = HashMap::new();
         params.insert("q".to_string(), self.q.to_string());
         params.insert("language".to_string(), self.language.to_string());
@@ -177,6 +181,14 @@ impl Search {
         Ok(request)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "search.get",
+            skip_all,
+            fields(q = %self.q, entity_type = self.entity_type.as_str())
+        )
+    )]
     pub async fn get(&self, api: &RestApi) -> Result<Vec<SearchResult>, RestApiError> {
         let request = self.generate_json_request(api).await?;
         let response = api.execute(request).await?;
@@ -194,12 +206,20 @@ impl Search {
             .collect()
     }
 
-    fn get_my_rest_api_path(&self) -> String {
-        format!(
-            "/{prefix}/{group}",
-            prefix = self.kind.path_prefix(),
-            group = self.entity_type.group_name()
-        )
+    fn get_my_rest_api_path(&self) -> Result<String, RestApiError> {
+        match (self.kind, &self.entity_type) {
+            (SearchKind::Search, EntityType::Item) => SearchItem {
+                q: &self.q,
+                language: &self.language.to_string(),
+            }
+            .path(),
+            // The bundled spec has no operation for these yet; they follow `searchItem`.
+            _ => Ok(format!(
+                "/{prefix}/{group}",
+                prefix = self.kind.path_prefix(),
+                group = self.entity_type.group_name()
+            )),
+        }
     }
 
     async fn filter_response_error(
@@ -253,19 +273,27 @@ mod tests {
     fn test_get_my_rest_api_path() {
         let en = Language::try_new("en").unwrap();
         assert_eq!(
-            Search::items("foo", en.clone()).get_my_rest_api_path(),
+            Search::items("foo", en.clone())
+                .get_my_rest_api_path()
+                .unwrap(),
             "/search/items"
         );
         assert_eq!(
-            Search::properties("foo", en.clone()).get_my_rest_api_path(),
+            Search::properties("foo", en.clone())
+                .get_my_rest_api_path()
+                .unwrap(),
             "/search/properties"
         );
         assert_eq!(
-            Search::suggest_items("foo", en.clone()).get_my_rest_api_path(),
+            Search::suggest_items("foo", en.clone())
+                .get_my_rest_api_path()
+                .unwrap(),
             "/suggest/items"
         );
         assert_eq!(
-            Search::suggest_properties("foo", en).get_my_rest_api_path(),
+            Search::suggest_properties("foo", en)
+                .get_my_rest_api_path()
+                .unwrap(),
             "/suggest/properties"
         );
     }
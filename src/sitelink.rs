@@ -4,6 +4,7 @@ use serde::ser::{Serialize, SerializeStruct};
 use serde_json::{json, Value};
 
 use crate::{
+    endpoints::{Endpoint, GetSitelink},
     EditMetadata, EntityId, HeaderInfo, HttpDelete, HttpGet, HttpMisc, HttpPut, RestApi,
     RestApiError, RevisionMatch,
 };
@@ -125,11 +126,11 @@ impl Sitelink {
     }
 
     fn get_rest_api_path_from_wiki(id: &EntityId, wiki: &str) -> Result<String, RestApiError> {
-        let wiki = SiteId::validated(wiki)?;
-        Ok(format!(
-            "/entities/{group}/{id}/sitelinks/{wiki}",
-            group = id.group()?
-        ))
+        GetSitelink {
+            item_id: id,
+            site_id: wiki,
+        }
+        .path()
     }
 }
 
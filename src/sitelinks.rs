@@ -1,6 +1,8 @@
 use crate::{
-    sitelinks_patch::SitelinksPatch, EntityId, FromJson, HeaderInfo, HttpGetEntity, HttpMisc,
-    RestApi, RestApiError, RevisionMatch, Sitelink,
+    endpoints::{Endpoint, GetSitelinks},
+    sitelinks_patch::SitelinksPatch,
+    EntityId, FromJson, HeaderInfo, HttpGetEntity, HttpMisc, RestApi, RestApiError, RevisionMatch,
+    Sitelink,
 };
 use derive_where::DeriveWhere;
 use serde::ser::{Serialize, SerializeMap};
@@ -16,10 +18,7 @@ pub struct Sitelinks {
 
 impl HttpMisc for Sitelinks {
     fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!(
-            "/entities/{group}/{id}/sitelinks",
-            group = id.group()?
-        ))
+        GetSitelinks { item_id: id }.path()
     }
 }
 
@@ -1,5 +1,7 @@
 use crate::{
-    patch_entry::PatchEntry, EntityId, HttpMisc, Patch, PatchApply, RestApiError, Sitelinks,
+    endpoints::{Endpoint, PatchSitelinks},
+    patch_entry::PatchEntry,
+    EntityId, HttpMisc, Patch, PatchApply, RestApiError, Sitelinks,
 };
 use serde::Serialize;
 use serde_json::Value;
@@ -43,8 +45,8 @@ impl Patch for SitelinksPatch {
 impl PatchApply<Sitelinks> for SitelinksPatch {}
 
 impl HttpMisc for SitelinksPatch {
-    fn get_my_rest_api_path(&self, id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!("/entities/{}/{id}/sitelinks", id.group()?))
+    fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
+        PatchSitelinks { item_id: id }.path()
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::{
+    endpoints::{Endpoint, GetStatement},
     property_value::{PropertyType, PropertyValue},
     statement_patch::StatementPatch,
     statement_value::StatementValue,
@@ -294,7 +295,7 @@ impl Statement {
     }
 
     fn get_rest_api_path_from_id(id: &str) -> Result<String, RestApiError> {
-        Ok(format!("/statements/{id}"))
+        GetStatement { statement_id: id }.path()
     }
 }
 
@@ -423,6 +424,29 @@ impl Statement {
             .iter()
             .all(|q| other.qualifiers().contains(q))
     }
+
+    /// Checks if this statement makes the same claim as another statement: same property, value,
+    /// rank, qualifiers and reference parts. Statement IDs, data types and reference hashes,
+    /// which the server fills in, are ignored.
+    pub fn same_content_as(&self, other: &Statement) -> bool {
+        self.property.id() == other.property.id()
+            && self.value == other.value
+            && self.rank == other.rank
+            && Self::same_property_values(&self.qualifiers, &other.qualifiers)
+            && self.references.len() == other.references.len()
+            && self
+                .references
+                .iter()
+                .zip(&other.references)
+                .all(|(a, b)| Self::same_property_values(a.parts(), b.parts()))
+    }
+
+    fn same_property_values(a: &[PropertyValue], b: &[PropertyValue]) -> bool {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(x, y)| x.property().id() == y.property().id() && x.value() == y.value())
+    }
 }
 
 #[cfg(test)]
@@ -432,6 +456,24 @@ mod tests {
     use wiremock::matchers::{body_partial_json, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
+    #[test]
+    fn test_same_content_as() {
+        let j: Value = serde_json::from_str(
+            &std::fs::read_to_string("test_data/test_statements_post.json").unwrap(),
+        )
+        .unwrap();
+        let posted = Statement::from_json(&j).unwrap();
+        let statement = Statement::new_string("P31", "Q5");
+        assert_ne!(posted, statement);
+        assert!(posted.same_content_as(&statement));
+        assert!(!posted.same_content_as(&Statement::new_string("P31", "Q1")));
+        let qualified = statement.with_qualifier(PropertyValue::new(
+            PropertyType::property("P580"),
+            StatementValue::new_string("2020"),
+        ));
+        assert!(!posted.same_content_as(&qualified));
+    }
+
     #[tokio::test]
     #[cfg_attr(miri, ignore)]
     async fn test_statement_get() {
@@ -1,6 +1,8 @@
 use crate::{
-    patch_entry::PatchEntry, EditMetadata, EntityId, FromJson, HttpMisc, Patch, PatchApply,
-    RestApi, RestApiError, Statement,
+    endpoints::{Endpoint, PatchStatement},
+    patch_entry::PatchEntry,
+    EditMetadata, EntityId, FromJson, HttpMisc, Patch, PatchApply, RestApi, RestApiError,
+    Statement,
 };
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -13,7 +15,10 @@ pub struct StatementPatch {
 
 impl HttpMisc for StatementPatch {
     fn get_my_rest_api_path(&self, _id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!("/statements/{id}", id = self.statement_id))
+        PatchStatement {
+            statement_id: &self.statement_id,
+        }
+        .path()
     }
 }
 
@@ -1,11 +1,17 @@
 use crate::{
-    patch_entry::PatchEntry, statements_patch::StatementsPatch, EditMetadata, EntityId, FromJson,
-    HeaderInfo, HttpGetEntity, HttpMisc, Patch, RestApi, RestApiError, RevisionMatch, Statement,
+    endpoints::{Endpoint, GetItemStatements, GetPropertyStatements},
+    patch_entry::PatchEntry,
+    statements_patch::StatementsPatch,
+    trace, EditMetadata, EntityId, FromJson, HeaderInfo, HttpGetEntity, HttpMisc, Patch, RestApi,
+    RestApiError, RetryContext, RevisionMatch, Statement,
 };
 use derive_where::DeriveWhere;
 use serde::ser::{Serialize, SerializeMap};
 use serde_json::{json, Value};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 #[derive(DeriveWhere, Debug, Clone, Default)]
 #[derive_where(PartialEq)]
@@ -269,6 +275,22 @@ impl Statements {
     }
 
     /// Posts a new statement to an entity with metadata
+    ///
+    /// A POST is not retried blindly on a 5xx error, as the server may have committed it anyway.
+    /// Only if the `RetryPolicy` opts into `reads_before_write` (and retries server errors) are
+    /// the statements of the entity for that property read before the first attempt. After a
+    /// 5xx they are read again: a statement that was not there before and makes the same claim
+    /// (see `Statement::same_content_as`) is the one that landed, and is returned. Otherwise
+    /// the statement is posted again, guarded with `If-Match` on the revision just read, as far
+    /// as the `RetryPolicy` allows. If a re-read fails, the original error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "statements.post",
+            skip_all,
+            fields(entity_id = %id, revision_id = tracing::field::Empty)
+        )
+    )]
     pub async fn post_meta(
         &self,
         id: &EntityId,
@@ -276,24 +298,124 @@ impl Statements {
         api: &RestApi,
         em: EditMetadata,
     ) -> Result<Statement, RestApiError> {
+        // The server assigns the statement ID.
         statement.set_id(None);
+        let property = statement.property().id().to_string();
+        if !api.reads_before_write(&reqwest::Method::POST) {
+            return self.post_once(id, &statement, api, &em).await;
+        }
+        // Without the statements before the write, a landed edit cannot be told apart from an
+        // identical claim that was already there, so the write is not retried.
+        let Ok(before) = Self::get_for_property(id, &property, api).await else {
+            return self.post_once(id, &statement, api, &em).await;
+        };
+        let started = Instant::now();
+        let mut attempt = 0;
+        let mut guarded = None;
+        loop {
+            let attempt_em = guarded.as_ref().unwrap_or(&em);
+            let result = self.post_once(id, &statement, api, attempt_em).await;
+            let Err(RestApiError::ApiError {
+                status, payload, ..
+            }) = &result
+            else {
+                return result;
+            };
+            // A guarded retry fails with 412 if the entity changed, e.g. as a late commit of an
+            // earlier attempt.
+            let precondition_failed =
+                guarded.is_some() && *status == reqwest::StatusCode::PRECONDITION_FAILED;
+            if !status.is_server_error() && !precondition_failed {
+                return result;
+            }
+            let Ok(current) = Self::get_for_property(id, &property, api).await else {
+                return result;
+            };
+            if let Some(landed) = Self::find_landed(&before, &current, &statement) {
+                return Ok(landed);
+            }
+            if precondition_failed {
+                return result;
+            }
+            // Posting again is only safe once the statement was found not to have landed.
+            let context = RetryContext::new(&reqwest::Method::POST, *status)
+                .with_error_code(Some(payload.code()))
+                .with_attempt(attempt)
+                .with_elapsed(started.elapsed())
+                .with_safe_to_repeat(true);
+            let Some(delay) = api.retry_policy().retry_delay(&context) else {
+                return result;
+            };
+            guarded = Some(Self::guard(&em, current.header_info()));
+            api.stats.record_retry(delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn post_once(
+        &self,
+        id: &EntityId,
+        statement: &Statement,
+        api: &RestApi,
+        em: &EditMetadata,
+    ) -> Result<Statement, RestApiError> {
         let j0 = json!({"statement": statement});
         let request = self
-            .generate_json_request(id, reqwest::Method::POST, j0, api, &em)
+            .generate_json_request(id, reqwest::Method::POST, j0, api, em)
             .await?;
         let response = api.execute(request).await?;
-        let (j, _statement_id) = self.filter_response_error(response).await?;
+        let (j, header_info) = self.filter_response_error(response).await?;
+        trace::record_revision_id(&header_info);
         // TODO add to self.statements?
         Statement::from_json(&j)
     }
+
+    /// Returns the statement in `current` that was not in `before` and makes the same claim
+    /// as `statement`, i.e. the statement a POST that may or may not have been committed
+    /// created.
+    fn find_landed(before: &Self, current: &Self, statement: &Statement) -> Option<Statement> {
+        let property = statement.property().id();
+        let existing: HashSet<&String> = before
+            .property(property)
+            .into_iter()
+            .filter_map(Statement::id)
+            .collect();
+        current
+            .property(property)
+            .into_iter()
+            .filter(|s| s.id().is_some_and(|id| !existing.contains(id)))
+            .find(|s| s.same_content_as(statement))
+            .cloned()
+    }
+
+    /// Returns the metadata for a retry: guarded with `If-Match` on the revision in
+    /// `header_info`, unless the caller set a base revision of their own.
+    fn guard(em: &EditMetadata, header_info: &HeaderInfo) -> EditMetadata {
+        let mut guarded = em.clone();
+        if em.revision_match().unmodified_since_revisions().is_empty() {
+            if let Some(revision) = header_info.revision_id() {
+                guarded.set_base_revision(revision);
+            }
+        }
+        guarded
+    }
 }
 
 impl HttpMisc for Statements {
     fn get_rest_api_path(id: &EntityId) -> Result<String, RestApiError> {
-        Ok(format!(
-            "/entities/{group}/{id}/statements",
-            group = id.group()?
-        ))
+        match id {
+            EntityId::Property(_) => GetPropertyStatements {
+                property_id: id,
+                property: None,
+            }
+            .path(),
+            _ => GetItemStatements {
+                item_id: id,
+                property: None,
+            }
+            .path(),
+        }
     }
 }
 
@@ -314,7 +436,7 @@ impl Serialize for Statements {
 mod tests {
     use crate::statement_value::StatementValue;
     use http::{HeaderMap, HeaderValue};
-    use wiremock::matchers::{bearer_token, body_partial_json, method, path};
+    use wiremock::matchers::{bearer_token, body_partial_json, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
@@ -389,6 +511,154 @@ mod tests {
         assert_eq!(statement.value(), &StatementValue::new_string("Q5"));
     }
 
+    /// Posts P31:Q5 to Q42 with a policy that reads before writes, and whose first attempt
+    /// fails with 503. The P31 statements read before the write are `before`, those read after
+    /// the 503 are `after` (or a 500 if `None`); `reposts` is the number of further POSTs
+    /// expected.
+    async fn post_after_server_error(
+        before: Value,
+        after: Option<Value>,
+        reposts: u64,
+    ) -> (MockServer, Result<Statement, RestApiError>) {
+        let v: Value = serde_json::from_str(
+            &std::fs::read_to_string("test_data/test_statements_post.json").unwrap(),
+        )
+        .unwrap();
+        let mock_path = "/w/rest.php/wikibase/v1/entities/items/Q42/statements";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(mock_path))
+            .and(query_param("property", "P31"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(before)
+                    .insert_header("ETag", "\"100\""),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let after = after.map_or_else(
+            || ResponseTemplate::new(500),
+            |after| {
+                ResponseTemplate::new(200)
+                    .set_body_json(after)
+                    .insert_header("ETag", "\"101\"")
+            },
+        );
+        Mock::given(method("GET"))
+            .and(path(mock_path))
+            .and(query_param("property", "P31"))
+            .respond_with(after)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(mock_path))
+            .and(wiremock::matchers::header("If-Match", "\"101\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&v))
+            .expect(reposts)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .with_retry_policy(
+                crate::ExponentialBackoff::default()
+                    .with_max_retries(2)
+                    .with_base_delay(std::time::Duration::from_millis(1))
+                    .with_read_before_write(true),
+            )
+            .build()
+            .unwrap();
+        let statement = Statement::new_string("P31", "Q5");
+        let posted = Statements::default()
+            .post(&EntityId::item("Q42"), statement, &api)
+            .await;
+        (mock_server, posted)
+    }
+
+    fn posted_statement_json() -> Value {
+        serde_json::from_str(
+            &std::fs::read_to_string("test_data/test_statements_post.json").unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_statements_post_landed_despite_server_error() {
+        let v = posted_statement_json();
+        let (mock_server, posted) =
+            post_after_server_error(json!({}), Some(json!({"P31": [v]})), 0).await;
+        assert_eq!(
+            posted.unwrap().id().unwrap(),
+            "Q42$6403c562-401a-2b26-85cc-8327801145e1"
+        );
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_statements_post_reposted_after_server_error() {
+        let (mock_server, posted) = post_after_server_error(json!({}), Some(json!({})), 1).await;
+        assert_eq!(posted.unwrap().value(), &StatementValue::new_string("Q5"));
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_statements_post_existing_claim_is_not_taken_as_landed() {
+        // An identical claim that was there before the write is not the one posted.
+        let v = posted_statement_json();
+        let existing = json!({"P31": [v]});
+        let (mock_server, posted) =
+            post_after_server_error(existing.clone(), Some(existing), 1).await;
+        assert!(posted.is_ok());
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_statements_post_not_read_by_default() {
+        let mock_path = "/w/rest.php/wikibase/v1/entities/items/Q42/statements";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(mock_path))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap();
+        let statement = Statement::new_string("P31", "Q5");
+        let posted = Statements::default()
+            .post(&EntityId::item("Q42"), statement, &api)
+            .await;
+        assert!(posted.is_err_and(|e| e.is_server_error()));
+        let received = mock_server.received_requests().await.unwrap();
+        assert!(received.iter().all(|r| r.method.as_str() == "POST"));
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_statements_post_reread_failure_returns_original_error() {
+        let (mock_server, posted) = post_after_server_error(json!({}), None, 0).await;
+        match posted {
+            Err(RestApiError::ApiError { status, .. }) => assert_eq!(status, 503),
+            other => panic!("Wrong result: {other:?}"),
+        }
+        mock_server.verify().await;
+    }
+
     #[tokio::test]
     #[cfg_attr(miri, ignore)]
     async fn test_eq() {
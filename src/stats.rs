@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Upper bounds of the latency histogram buckets. Latencies above the last bound fall into a
+/// final overflow bucket.
+const LATENCY_BUCKETS: [Duration; 10] = [
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// The kind of endpoint a request went to, used to group latencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EndpointGroup {
+    /// Entities and their labels, descriptions and aliases
+    Entities,
+    Statements,
+    Search,
+    Sitelinks,
+    /// Anything else, e.g. `openapi.json` or property data types
+    Other,
+}
+
+impl EndpointGroup {
+    /// Classifies a request by its URL path.
+    pub fn from_path(path: &str) -> Self {
+        if path.contains("/statements") {
+            EndpointGroup::Statements
+        } else if path.contains("/sitelinks") {
+            EndpointGroup::Sitelinks
+        } else if path.contains("/search/") || path.contains("/suggest/") {
+            EndpointGroup::Search
+        } else if path.contains("/entities/") {
+            EndpointGroup::Entities
+        } else {
+            EndpointGroup::Other
+        }
+    }
+
+    pub const fn as_str(&self) -> &str {
+        match self {
+            EndpointGroup::Entities => "entities",
+            EndpointGroup::Statements => "statements",
+            EndpointGroup::Search => "search",
+            EndpointGroup::Sitelinks => "sitelinks",
+            EndpointGroup::Other => "other",
+        }
+    }
+}
+
+/// A fixed-bucket latency histogram.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// Returns the upper bound of each bucket; `counts()` has one more (overflow) entry.
+    pub const fn bucket_bounds() -> &'static [Duration] {
+        &LATENCY_BUCKETS
+    }
+
+    /// Returns the number of requests per bucket, the last one counting everything above
+    /// the largest bound.
+    pub const fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the number of recorded requests.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the mean latency, or zero if nothing was recorded.
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.count()) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total / count,
+            Err(_) => self.total.div_f64(self.count() as f64),
+        }
+    }
+
+    /// Returns the highest recorded latency.
+    pub const fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the upper bound of the bucket that contains the `quantile` (0.0 to 1.0), e.g.
+    /// `0.95` for the 95th percentile. For the overflow bucket, this is the maximum latency.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = (quantile.clamp(0.0, 1.0) * count as f64).ceil().max(1.0);
+        let mut seen = 0;
+        for (bucket, bucket_count) in self.counts.iter().enumerate() {
+            seen += bucket_count;
+            if seen as f64 >= rank {
+                return LATENCY_BUCKETS.get(bucket).copied().unwrap_or(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// A snapshot of the request counters of a `RestApi`, since it was built. Returned by
+/// `RestApi::stats()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestStats {
+    requests: HashMap<(reqwest::Method, reqwest::StatusCode), u64>,
+    transport_errors: u64,
+    retries: u64,
+    rate_limited: u64,
+    backoff: Duration,
+    bytes_sent: u64,
+    bytes_received: u64,
+    token_renewals: u64,
+    latencies: HashMap<EndpointGroup, LatencyHistogram>,
+}
+
+impl RequestStats {
+    /// Returns the number of responses, by request method and response status.
+    /// Every attempt counts, including retried ones.
+    pub const fn requests(&self) -> &HashMap<(reqwest::Method, reqwest::StatusCode), u64> {
+        &self.requests
+    }
+
+    /// Returns the total number of responses received.
+    pub fn total_requests(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Returns the number of requests that got no response at all (e.g. connection errors).
+    pub const fn transport_errors(&self) -> u64 {
+        self.transport_errors
+    }
+
+    /// Returns the number of retries.
+    pub const fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Returns the number of 429 (Too Many Requests) responses.
+    pub const fn rate_limited(&self) -> u64 {
+        self.rate_limited
+    }
+
+    /// Returns the total time spent waiting before retries.
+    pub const fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Returns the number of request body bytes sent.
+    pub const fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the number of response body bytes declared by `Content-Length`. The bodies are
+    /// not measured as they are read, so responses without that header (e.g. chunked or
+    /// decompressed ones) add nothing.
+    pub const fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Returns the number of `OAuth2` access token renewals.
+    pub const fn token_renewals(&self) -> u64 {
+        self.token_renewals
+    }
+
+    /// Returns the latency histogram for an endpoint group, if any request went there.
+    pub fn latency(&self, group: EndpointGroup) -> Option<&LatencyHistogram> {
+        self.latencies.get(&group)
+    }
+}
+
+impl Display for RequestStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "requests: {} ({} transport errors), 429 responses: {}, retries: {}, backoff: {:.1}s",
+            self.total_requests(),
+            self.transport_errors,
+            self.rate_limited,
+            self.retries,
+            self.backoff.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "bytes sent: {}, bytes received (Content-Length): {}, token renewals: {}",
+            self.bytes_sent, self.bytes_received, self.token_renewals
+        )?;
+        let mut requests: Vec<_> = self.requests.iter().collect();
+        requests.sort_by_key(|((method, status), _)| (method.to_string(), *status));
+        for ((method, status), count) in requests {
+            writeln!(f, "  {method} {}: {count}", status.as_u16())?;
+        }
+        let mut latencies: Vec<_> = self.latencies.iter().collect();
+        latencies.sort_by_key(|(group, _)| **group);
+        for (group, histogram) in latencies {
+            writeln!(
+                f,
+                "  {}: {} requests, mean {}ms, p95 <= {}ms, max {}ms",
+                group.as_str(),
+                histogram.count(),
+                histogram.mean().as_millis(),
+                histogram.quantile(0.95).as_millis(),
+                histogram.max().as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The live counters, shared by all clones of a `RestApi`.
+#[derive(Debug, Default)]
+pub(crate) struct StatsRecorder {
+    stats: Mutex<RequestStats>,
+}
+
+impl StatsRecorder {
+    pub(crate) fn snapshot(&self) -> RequestStats {
+        self.stats
+            .lock()
+            .map(|stats| stats.clone())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RequestStats)) {
+        // A poisoned lock means a panic elsewhere; losing a sample is better than another panic.
+        if let Ok(mut stats) = self.stats.lock() {
+            f(&mut stats);
+        }
+    }
+
+    /// Records one exchange with the server. `response` is `None` if no response arrived.
+    pub(crate) fn record_exchange(
+        &self,
+        method: &reqwest::Method,
+        path: &str,
+        bytes_sent: u64,
+        response: Option<&reqwest::Response>,
+        latency: Duration,
+    ) {
+        self.update(|stats| {
+            stats.bytes_sent += bytes_sent;
+            let Some(response) = response else {
+                stats.transport_errors += 1;
+                return;
+            };
+            let status = response.status();
+            *stats.requests.entry((method.clone(), status)).or_default() += 1;
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                stats.rate_limited += 1;
+            }
+            stats.bytes_received += response.content_length().unwrap_or(0);
+            stats
+                .latencies
+                .entry(EndpointGroup::from_path(path))
+                .or_default()
+                .record(latency);
+        });
+    }
+
+    pub(crate) fn record_retry(&self, delay: Duration) {
+        self.update(|stats| {
+            stats.retries += 1;
+            stats.backoff += delay;
+        });
+    }
+
+    pub(crate) fn record_token_renewal(&self) {
+        self.update(|stats| stats.token_renewals += 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RestApi;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_endpoint_group_from_path() {
+        let groups = [
+            (
+                "/w/rest.php/wikibase/v1/entities/items/Q42",
+                EndpointGroup::Entities,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/entities/items/Q42/labels/en",
+                EndpointGroup::Entities,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/entities/items/Q42/statements",
+                EndpointGroup::Statements,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/statements/Q42$1",
+                EndpointGroup::Statements,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/entities/items/Q42/sitelinks/enwiki",
+                EndpointGroup::Sitelinks,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/search/items",
+                EndpointGroup::Search,
+            ),
+            (
+                "/w/rest.php/wikibase/v1/suggest/properties",
+                EndpointGroup::Search,
+            ),
+            ("/w/rest.php/wikibase/v1/openapi.json", EndpointGroup::Other),
+        ];
+        for (path, group) in groups {
+            assert_eq!(EndpointGroup::from_path(path), group, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+        for ms in [5, 20, 20, 80, 20_000] {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.counts()[0], 1);
+        assert_eq!(histogram.counts()[1], 2);
+        assert_eq!(
+            histogram.counts()[LatencyHistogram::bucket_bounds().len()],
+            1
+        );
+        assert_eq!(histogram.quantile(0.5), Duration::from_millis(25));
+        assert_eq!(histogram.quantile(1.0), Duration::from_secs(20));
+        assert_eq!(histogram.max(), Duration::from_secs(20));
+        assert_eq!(histogram.mean(), Duration::from_millis(4025));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_stats_are_shared_by_clones() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/openapi.json"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/openapi.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap();
+        api.clone().get_openapi_json().await.unwrap();
+
+        let stats = api.stats();
+        assert_eq!(stats.total_requests(), 2);
+        let get = reqwest::Method::GET;
+        assert_eq!(
+            stats.requests()[&(get.clone(), reqwest::StatusCode::TOO_MANY_REQUESTS)],
+            1
+        );
+        assert_eq!(stats.requests()[&(get, reqwest::StatusCode::OK)], 1);
+        assert_eq!(stats.retries(), 1);
+        assert_eq!(stats.rate_limited(), 1);
+        assert_eq!(stats.backoff(), Duration::ZERO);
+        assert_eq!(stats.bytes_received(), 2);
+        assert_eq!(stats.token_renewals(), 0);
+        assert_eq!(stats.latency(EndpointGroup::Other).unwrap().count(), 2);
+        assert!(stats.latency(EndpointGroup::Entities).is_none());
+        let summary = stats.to_string();
+        assert!(summary.contains("429 responses: 1, retries: 1"));
+        assert!(summary.contains("GET 429: 1"));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{secret::Secret, RestApiError};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The `OAuth2` tokens a `TokenStore` keeps between runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredTokens {
+    #[serde(serialize_with = "expose_secret")]
+    access_token: Option<Secret>,
+    #[serde(serialize_with = "expose_secret")]
+    refresh_token: Option<Secret>,
+    /// When the access token is due for renewal, in seconds since the Unix epoch.
+    #[serde(default)]
+    renew_at: Option<u64>,
+    /// When the access token expires, in seconds since the Unix epoch.
+    #[serde(default)]
+    expires_at: Option<u64>,
+    /// When the refresh token expires, in seconds since the Unix epoch.
+    #[serde(default)]
+    refresh_token_expires_at: Option<u64>,
+    /// The scopes granted with the access token.
+    #[serde(default)]
+    granted_scopes: Vec<String>,
+}
+
+impl StoredTokens {
+    /// Creates stored tokens. `renew_in` is how long the access token stays fresh.
+    pub fn new(
+        access_token: Option<Secret>,
+        refresh_token: Option<Secret>,
+        renew_in: Option<Duration>,
+    ) -> Self {
+        let renew_at = renew_in
+            .and_then(|renew_in| SystemTime::now().checked_add(renew_in))
+            .and_then(to_unix_secs);
+        Self {
+            access_token,
+            refresh_token,
+            renew_at,
+            expires_at: None,
+            refresh_token_expires_at: None,
+            granted_scopes: vec![],
+        }
+    }
+
+    /// Sets when the access token and the refresh token expire.
+    #[must_use]
+    pub fn with_expiry(
+        mut self,
+        expires_at: Option<SystemTime>,
+        refresh_token_expires_at: Option<SystemTime>,
+    ) -> Self {
+        self.expires_at = expires_at.and_then(to_unix_secs);
+        self.refresh_token_expires_at = refresh_token_expires_at.and_then(to_unix_secs);
+        self
+    }
+
+    /// Sets the scopes granted with the access token.
+    #[must_use]
+    pub fn with_granted_scopes<I, S>(mut self, granted_scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.granted_scopes = granted_scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns the access token.
+    pub const fn access_token(&self) -> Option<&Secret> {
+        self.access_token.as_ref()
+    }
+
+    /// Returns the refresh token.
+    pub const fn refresh_token(&self) -> Option<&Secret> {
+        self.refresh_token.as_ref()
+    }
+
+    /// Returns how long the access token stays fresh, or `None` if it is due for renewal (or
+    /// its age is unknown).
+    pub fn renew_in(&self) -> Option<Duration> {
+        let renew_at = from_unix_secs(self.renew_at?)?;
+        renew_at
+            .duration_since(SystemTime::now())
+            .ok()
+            .filter(|renew_in| !renew_in.is_zero())
+    }
+
+    /// Returns when the access token expires, if known.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        from_unix_secs(self.expires_at?)
+    }
+
+    /// Returns when the refresh token expires, if known.
+    pub fn refresh_token_expires_at(&self) -> Option<SystemTime> {
+        from_unix_secs(self.refresh_token_expires_at?)
+    }
+
+    /// Returns the scopes granted with the access token (empty if not known).
+    pub fn granted_scopes(&self) -> &[String] {
+        &self.granted_scopes
+    }
+}
+
+/// Writes the plain credential; `StoredTokens` is the only place a `Secret` is serialized.
+fn expose_secret<S: Serializer>(secret: &Option<Secret>, serializer: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(Secret::expose).serialize(serializer)
+}
+
+fn to_unix_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since| since.as_secs())
+}
+
+fn from_unix_secs(secs: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Keeps `OAuth2` tokens between runs, so a restarted process does not need a new
+/// authorization.
+///
+/// Set it with `RestApiBuilder::with_token_store`. The stored tokens are loaded when the
+/// `RestApi` is built, and saved whenever the access token is obtained or renewed.
+pub trait TokenStore: Debug + Send + Sync {
+    /// Returns the stored tokens, or `None` if there are none yet.
+    /// # Errors
+    /// Returns an error if the store cannot be read.
+    fn load(&self) -> Result<Option<StoredTokens>, RestApiError>;
+
+    /// Replaces the stored tokens.
+    /// # Errors
+    /// Returns an error if the store cannot be written.
+    fn save(&self, tokens: &StoredTokens) -> Result<(), RestApiError>;
+}
+
+/// A `TokenStore` in a JSON file. On Unix, the file is only readable by its owner.
+///
+/// Saving writes a temporary file in the same directory and renames it over the token file,
+/// so a crash never leaves a truncated file, and the permissions of an existing file are
+/// tightened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Stores tokens in `path`; the file is created on the first save.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the path of the token file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn error(&self, action: &str, e: &std::io::Error) -> RestApiError {
+        RestApiError::TokenStore(format!("cannot {action} {}: {e}", self.path.display()))
+    }
+
+    /// Replaces the file atomically with `text`.
+    fn write(&self, text: &str) -> std::io::Result<()> {
+        use std::io::Write;
+        let file_name = self.path.file_name().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file path")
+        })?;
+        let temporary = self.path.with_file_name(format!(
+            ".{}.{}.tmp",
+            file_name.to_string_lossy(),
+            uuid::Uuid::new_v4()
+        ));
+        let result = Self::create_private(&temporary)
+            .and_then(|mut file| {
+                file.write_all(text.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temporary, &self.path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// Creates a new file that only its owner can read.
+    #[cfg(unix)]
+    fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+        use std::os::unix::fs::OpenOptionsExt;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+    }
+
+    /// Creates a new file.
+    #[cfg(not(unix))]
+    fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>, RestApiError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(self.error("read", &e)),
+        }
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<(), RestApiError> {
+        let text = serde_json::to_string_pretty(tokens)?;
+        self.write(&text).map_err(|e| self.error("write", &e))
+    }
+}
+
+/// A `TokenStore` in memory, e.g. to share tokens between `RestApi`s or in tests.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<Option<StoredTokens>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store holding `tokens`.
+    pub const fn with_tokens(tokens: StoredTokens) -> Self {
+        Self {
+            tokens: Mutex::new(Some(tokens)),
+        }
+    }
+
+    /// Returns the stored tokens.
+    pub fn tokens(&self) -> Option<StoredTokens> {
+        self.tokens.lock().ok()?.clone()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Result<Option<StoredTokens>, RestApiError> {
+        let tokens = self
+            .tokens
+            .lock()
+            .map_err(|e| RestApiError::TokenStore(e.to_string()))?;
+        Ok(tokens.clone())
+    }
+
+    fn save(&self, tokens: &StoredTokens) -> Result<(), RestApiError> {
+        let mut stored = self
+            .tokens
+            .lock()
+            .map_err(|e| RestApiError::TokenStore(e.to_string()))?;
+        *stored = Some(tokens.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renew_in() {
+        let fresh = StoredTokens::new(
+            Some("access".into()),
+            Some("refresh".into()),
+            Some(Duration::from_secs(3600)),
+        );
+        let renew_in = fresh.renew_in().unwrap();
+        assert!(renew_in > Duration::from_secs(3590) && renew_in <= Duration::from_secs(3600));
+        assert_eq!(fresh.access_token().map(Secret::expose), Some("access"));
+        assert_eq!(fresh.refresh_token().map(Secret::expose), Some("refresh"));
+
+        let unknown = StoredTokens::new(None, Some("refresh".into()), None);
+        assert_eq!(unknown.renew_in(), None);
+        let expired: StoredTokens =
+            serde_json::from_str(r#"{"access_token":"a","refresh_token":"r","renew_at":1}"#)
+                .unwrap();
+        assert_eq!(expired.renew_in(), None);
+        assert_eq!(expired.expires_at(), None);
+    }
+
+    #[test]
+    fn test_with_expiry() {
+        let expires_at = UNIX_EPOCH + Duration::from_secs(1_900_000_000);
+        let refresh_token_expires_at = UNIX_EPOCH + Duration::from_secs(1_950_000_000);
+        let tokens = StoredTokens::new(Some("access".into()), Some("refresh".into()), None)
+            .with_expiry(Some(expires_at), Some(refresh_token_expires_at));
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert!(json.contains(r#""expires_at":1900000000"#));
+        let loaded: StoredTokens = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.expires_at(), Some(expires_at));
+        assert_eq!(
+            loaded.refresh_token_expires_at(),
+            Some(refresh_token_expires_at)
+        );
+    }
+
+    #[test]
+    fn test_memory_token_store() {
+        let store = MemoryTokenStore::new();
+        assert_eq!(store.load().unwrap(), None);
+        let tokens = StoredTokens::new(Some("access".into()), None, None);
+        store.save(&tokens).unwrap();
+        assert_eq!(store.load().unwrap(), Some(tokens.clone()));
+        assert_eq!(store.tokens(), Some(tokens));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_file_token_store() {
+        let path = std::env::temp_dir().join(format!(
+            "wikibase_rest_api_tokens_{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        let store = FileTokenStore::new(&path);
+        assert_eq!(store.path(), path);
+        assert_eq!(store.load().unwrap(), None);
+        let tokens = StoredTokens::new(
+            Some("access".into()),
+            Some("refresh".into()),
+            Some(Duration::from_secs(60)),
+        )
+        .with_granted_scopes(["basic", "editpage"]);
+        store.save(&tokens).unwrap();
+        // A new store (e.g. after a restart) reads the same tokens.
+        assert_eq!(FileTokenStore::new(&path).load().unwrap(), Some(tokens));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_file_token_store_replaces_existing_file() {
+        let directory =
+            std::env::temp_dir().join(format!("wikibase_rest_api_tokens_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&directory).unwrap();
+        let path = directory.join("tokens.json");
+        std::fs::write(&path, "{}").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let readable = std::fs::Permissions::from_mode(0o644);
+            std::fs::set_permissions(&path, readable).unwrap();
+        }
+        let store = FileTokenStore::new(&path);
+        let tokens = StoredTokens::new(Some("access".into()), None, None);
+        store.save(&tokens).unwrap();
+        assert_eq!(store.load().unwrap(), Some(tokens));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // No temporary file is left behind.
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Helpers for the optional `tracing` feature. Spans are opened with
+//! `#[cfg_attr(feature = "tracing", tracing::instrument(...))]` on the operations themselves;
+//! the functions here record fields and emit events on the current span, and compile to
+//! nothing when the feature is off.
+
+// Without the feature the bodies are empty, which clippy would rather see as `const fn`.
+#![cfg_attr(not(feature = "tracing"), allow(clippy::missing_const_for_fn))]
+
+use crate::{dry_run::PlannedWrite, HeaderInfo};
+use std::time::Duration;
+
+/// Records the status of a response and the (zero-based) attempt that produced it.
+pub(crate) fn record_response(status: reqwest::StatusCode, attempt: u32) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("status", status.as_u16());
+        span.record("attempt", attempt);
+        tracing::debug!(status = status.as_u16(), attempt, "response");
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (status, attempt);
+}
+
+/// Records the delay chosen before retrying a request that failed with `status`.
+pub(crate) fn record_retry(status: reqwest::StatusCode, attempt: u32, delay: Duration) {
+    #[cfg(feature = "tracing")]
+    {
+        let delay_ms = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        tracing::Span::current().record("retry_delay_ms", delay_ms);
+        tracing::info!(status = status.as_u16(), attempt, delay_ms, "retrying");
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (status, attempt, delay);
+}
+
+/// Records the revision ID from a response, if the server sent one.
+pub(crate) fn record_revision_id(header_info: &HeaderInfo) {
+    #[cfg(feature = "tracing")]
+    if let Some(revision_id) = header_info.revision_id() {
+        tracing::Span::current().record("revision_id", revision_id);
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = header_info;
+}
+
+/// Emits an event for one `EntityContainer` sweep over `ids` entities of `kind`.
+pub(crate) fn sweep(kind: &str, round: usize, ids: usize, concurrency: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(kind, round, ids, concurrency, "sweep");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (kind, round, ids, concurrency);
+}
+
+/// Records the outcome counts of an `EntityContainer` load.
+pub(crate) fn record_load(loaded: usize, missing: usize, failed: usize) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("loaded", loaded);
+        span.record("missing", missing);
+        span.record("failed", failed);
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (loaded, missing, failed);
+}
+
+/// Emits an event for a write that dry-run mode captured instead of sending.
+pub(crate) fn planned_write(write: &PlannedWrite) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(method = %write.method(), path = write.path(), "dry run: {write}");
+    #[cfg(not(feature = "tracing"))]
+    let _ = write;
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{entity::Entity, EntityId, Item, RestApi};
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+    use tracing_core::span::Current;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A span as the subscriber saw it: its name, parent and recorded fields.
+    #[derive(Debug, Clone)]
+    struct CapturedSpan {
+        metadata: &'static Metadata<'static>,
+        parent: Option<u64>,
+        fields: HashMap<String, String>,
+    }
+
+    impl Visit for CapturedSpan {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    /// Captures every span, in a single-threaded test.
+    #[derive(Default, Clone)]
+    struct Capture {
+        spans: Arc<Mutex<Vec<CapturedSpan>>>,
+        entered: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Capture {
+        fn spans(&self) -> MutexGuard<'_, Vec<CapturedSpan>> {
+            self.spans.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        fn entered(&self) -> MutexGuard<'_, Vec<u64>> {
+            self.entered.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        fn span(&self, name: &str) -> CapturedSpan {
+            self.spans()
+                .iter()
+                .find(|span| span.metadata.name() == name)
+                .cloned()
+                .unwrap_or_else(|| panic!("no {name} span"))
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let parent = match attributes.parent() {
+                Some(parent) => Some(parent.into_u64()),
+                None if attributes.is_contextual() => self.entered().last().copied(),
+                None => None,
+            };
+            let mut span = CapturedSpan {
+                metadata: attributes.metadata(),
+                parent,
+                fields: HashMap::new(),
+            };
+            attributes.record(&mut span);
+            let mut spans = self.spans();
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let index = span.into_u64() as usize - 1;
+            values.record(&mut self.spans()[index]);
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.entered().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered().pop();
+        }
+
+        fn current_span(&self) -> Current {
+            let Some(id) = self.entered().last().copied() else {
+                return Current::none();
+            };
+            let metadata = self.spans()[id as usize - 1].metadata;
+            Current::new(Id::from_u64(id), metadata)
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_request_span_and_revision_id() {
+        let v = std::fs::read_to_string("test_data/Q42.json").unwrap();
+        let v: serde_json::Value = serde_json::from_str(&v).unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/w/rest.php/wikibase/v1/entities/items/Q42"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"1234\"")
+                    .set_body_json(v),
+            )
+            .mount(&mock_server)
+            .await;
+        let api = RestApi::builder(&(mock_server.uri() + "/w/rest.php"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let capture = Capture::default();
+        let guard = tracing::subscriber::set_default(capture.clone());
+        Item::get(EntityId::item("Q42"), &api).await.unwrap();
+        drop(guard);
+
+        let entity = capture.span("entity.get");
+        assert_eq!(entity.fields["entity_id"], "Q42");
+        assert_eq!(entity.fields["revision_id"], "1234");
+        let request = capture.span("request");
+        assert_eq!(request.fields["method"], "GET");
+        assert_eq!(
+            request.fields["path"],
+            "/w/rest.php/wikibase/v1/entities/items/Q42"
+        );
+        assert_eq!(request.fields["status"], "200");
+        assert_eq!(request.fields["attempt"], "0");
+        // The request is sent inside the operation's span.
+        let spans = capture.spans();
+        let request_parent = request.parent.unwrap();
+        assert_eq!(
+            spans[request_parent as usize - 1].metadata.name(),
+            "entity.get"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::RestApiError;
+use futures::future::BoxFuture;
+use std::fmt::Debug;
+
+/// Sends a single, fully built HTTP request and returns the raw response.
+///
+/// `RestApi` performs retries, token renewal and `Retry-After` handling on top of the
+/// transport, so an implementation only has to do one exchange. Non-success statuses must
+/// be returned as `Ok(response)`; `Err` is reserved for failures to get any response at all.
+///
+/// Use this to plug in an in-process fake, a recording layer, or a different HTTP stack via
+/// `RestApiBuilder::with_transport`.
+pub trait Transport: Debug + Send + Sync {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, Result<reqwest::Response, RestApiError>>;
+}
+
+/// The default transport, sending requests through a `reqwest::Client`.
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a new transport from a `reqwest::Client`.
+    pub const fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    /// Returns the underlying `reqwest::Client`.
+    pub const fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, Result<reqwest::Response, RestApiError>> {
+        Box::pin(async move { Ok(self.client.execute(request).await?) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RestApi, Transport};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    /// An in-process fake that answers with queued statuses, then 200 echoing the request path.
+    #[derive(Debug, Default)]
+    struct FakeTransport {
+        statuses: Vec<u16>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Transport for FakeTransport {
+        fn execute(
+            &self,
+            request: reqwest::Request,
+        ) -> BoxFuture<'_, Result<reqwest::Response, RestApiError>> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let status = self.statuses.get(call).copied().unwrap_or(200);
+            let body = format!("{{\"path\":\"{}\"}}", request.url().path());
+            Box::pin(async move {
+                let response = http::Response::builder()
+                    .status(status)
+                    .header("Retry-After", "0")
+                    .body(body)
+                    .map_err(|e| RestApiError::EmptyValue(e.to_string()))?;
+                Ok(reqwest::Response::from(response))
+            })
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_custom_transport_is_used() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_transport(FakeTransport {
+                statuses: vec![],
+                calls: calls.clone(),
+            })
+            .build()
+            .unwrap();
+        let json = api.get_openapi_json().await.unwrap();
+        assert_eq!(json["path"], "/w/rest.php/wikibase/v1/openapi.json");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_retries_run_on_top_of_transport() {
+        // Two 503s, then success: RestApi retries through the fake transport.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_transport(FakeTransport {
+                statuses: vec![503, 503],
+                calls: calls.clone(),
+            })
+            .with_retry_base_delay(Duration::from_millis(1))
+            .build()
+            .unwrap();
+        assert!(api.get_openapi_json().await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_token_requests_use_transport() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let api = RestApi::builder("https://test.wikidata.org/w/rest.php")
+            .unwrap()
+            .with_transport(FakeTransport {
+                statuses: vec![],
+                calls: calls.clone(),
+            })
+            .with_oauth2_info("id", "secret")
+            .build()
+            .unwrap();
+        // The fake answers without tokens, so the exchange fails; but it must go via the transport.
+        let result = api
+            .token()
+            .write()
+            .await
+            .get_access_token(&api, "code")
+            .await;
+        assert!(matches!(result, Err(RestApiError::AccessTokenRequired)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_reqwest_transport_client() {
+        let client = reqwest::Client::new();
+        let transport = ReqwestTransport::new(client.clone());
+        assert_eq!(format!("{:?}", transport.client()), format!("{client:?}"));
+    }
+}